@@ -1,6 +1,6 @@
-#![feature(array_chunks)]
+#![allow(dead_code)]
 use image::ImageReader;
-use ql_raster::{commands::AdvancedMode, prelude::*};
+use ql_raster::prelude::*;
 use std::time::Duration;
 
 fn main() {
@@ -26,8 +26,8 @@ fn main() {
 fn print(printer: &mut PTouchPrinter<PTouchTcpInterface>) -> Result<()> {
     // let demo_line = [0x55; 40];
     // let demo_line2 = [0xaa; 40];
-    let demo_line = [0xf0; 80];
-    let demo_line2 = [0x0f; 80];
+    // let demo_line = [0xf0; 80];
+    // let demo_line2 = [0x0f; 80];
 
     printer.invalidate()?;
     printer.init()?;
@@ -38,6 +38,8 @@ fn print(printer: &mut PTouchPrinter<PTouchTcpInterface>) -> Result<()> {
         width: Some(29), // needs to be correct value for installed label type!
         length: None,
         raster_no: 1,
+        quality: false,
+        first_page: true,
         recover: true,
     };
     printer.set_print_info(&pi)?;
@@ -66,8 +68,8 @@ fn print(printer: &mut PTouchPrinter<PTouchTcpInterface>) -> Result<()> {
 fn get_line(y: u16, width: usize) -> Vec<u8> {
     let bytes: Vec<u8> = (0..width).map(|x| x as u8 ^ y as u8).collect();
     let bitmap: Vec<u8> = bytes
-        .array_chunks::<8>()
-        .map(|chunk| bytes_to_bitmap(chunk, y as u8))
+        .chunks_exact(8)
+        .map(|chunk| bytes_to_bitmap(chunk.try_into().unwrap(), y as u8))
         .collect();
 
     bitmap
@@ -77,8 +79,8 @@ fn bytes_to_bitmap(bytes: &[u8; 8], threshold: u8) -> u8 {
     let thresh = |val, shift| if val > threshold { 1u8 } else { 0 } << shift;
 
     let mut out = 0;
-    for bit in 0..7 {
-        out |= thresh(bytes[bit], bit)
+    for (bit, val) in bytes.iter().enumerate().take(7) {
+        out |= thresh(*val, bit)
     }
 
     out
@@ -0,0 +1,9 @@
+use ql_raster::disasm;
+
+/// Dump a captured command stream, e.g. `cargo run --example dump_prn -- label.prn`
+fn main() {
+    let path = std::env::args().nth(1).expect("Usage: dump_prn <file.prn>");
+    let data = std::fs::read(path).expect("Unable to read file!");
+
+    print!("{}", disasm::disassemble(&data));
+}
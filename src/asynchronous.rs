@@ -0,0 +1,832 @@
+//! Async (tokio) printer API, enabled with the `async` feature.
+//!
+//! `AsyncPTouchInterface`, `AsyncCommands` and `AsyncGetStatus` are the async counterparts of
+//! `PTouchInterface`, `Commands` and `GetStatus`, implemented by `AsyncPTouchPrinter`. Print jobs,
+//! media and status are shared with the sync API. `AsyncSnmpMonitor` polls the status over SNMP.
+//!
+//! ```no_run
+//! use ql_raster::{asynchronous::{self, AsyncGetStatus}, job::PrintJob, prelude::*};
+//! use std::time::Duration;
+//!
+//! # async fn print() -> Result<()> {
+//! let mut printer = asynchronous::from_addr("labelprinter:9100").await?;
+//! let media = Media::from_status(&printer.get_status().await?).expect("Unknown media");
+//!
+//! let job = PrintJob::new().media(media).page(RasterBuffer::new(300));
+//! printer.print_job_and_wait(&job, Duration::from_secs(30)).await?;
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    future::Future,
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::atomic::{AtomicI32, Ordering},
+    time::Duration,
+};
+
+use snmp::{pdu, SnmpMessageType, SnmpPdu};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpStream, ToSocketAddrs, UdpSocket},
+    time::{self, Instant},
+};
+
+use crate::{
+    commands::{AdvancedMode, Command, CompressionMode, Mode, PrintInfo, VariousMode},
+    emulator::Emulator,
+    interface::{PTouchInterface, SnmpSettings},
+    job::{self, JobOutcome, NotificationTracker, PrintJob},
+    monitor::{JobTracker, PrinterState, SnmpValue},
+    printer::{snmp_oid, Protocol, DEFAULT_BUFFER_LIMIT},
+    raster::Engine,
+    status::{Capabilities, DeviceStatus, Model, Status},
+    PTouchError, Result, SnmpError,
+};
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1500);
+
+/// Async counterpart of `PTouchInterface`
+pub trait AsyncPTouchInterface: Send {
+    fn name(&self) -> String;
+
+    fn read(&mut self, buf: &mut [u8]) -> impl Future<Output = Result<usize>> + Send;
+
+    fn read_vec(&mut self) -> impl Future<Output = Result<Vec<u8>>> + Send;
+
+    fn write(&mut self, data: &[u8]) -> impl Future<Output = Result<()>> + Send;
+
+    fn flush(&mut self) -> impl Future<Output = Result<()>> + Send {
+        async { Ok(()) }
+    }
+}
+
+/// Async counterpart of `PTouchTcpInterface`
+#[derive(Debug)]
+pub struct AsyncPTouchTcpInterface {
+    stream: TcpStream,
+    ip_addr: IpAddr,
+    read_timeout: Option<Duration>,
+    snmp: SnmpSettings,
+}
+
+impl AsyncPTouchTcpInterface {
+    pub async fn new<A: ToSocketAddrs>(addr: A, read_timeout: Option<Duration>) -> Result<Self> {
+        let stream = TcpStream::connect(addr).await?;
+        let ip_addr = stream.peer_addr()?.ip();
+
+        Ok(Self {
+            stream,
+            ip_addr,
+            read_timeout,
+            snmp: SnmpSettings::default(),
+        })
+    }
+
+    pub fn snmp_settings(&self) -> &SnmpSettings {
+        &self.snmp
+    }
+
+    pub fn set_snmp_settings(&mut self, settings: SnmpSettings) {
+        self.snmp = settings;
+    }
+
+    /// Read, failing with `io::ErrorKind::TimedOut` after the read timeout like a sync socket
+    async fn read_with_timeout(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.read_timeout {
+            Some(timeout) => time::timeout(timeout, self.stream.read(buf))
+                .await
+                .unwrap_or_else(|_| Err(io::ErrorKind::TimedOut.into())),
+            None => self.stream.read(buf).await,
+        }
+    }
+}
+
+impl AsyncPTouchInterface for AsyncPTouchTcpInterface {
+    fn name(&self) -> String {
+        format!(
+            "PTouch async TCP interface on {}",
+            self.stream
+                .peer_addr()
+                .map(|sa| sa.to_string())
+                .unwrap_or_default()
+        )
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        Ok(self.read_with_timeout(buf).await?)
+    }
+
+    /// Everything received until the connection is closed or the read timeout expires
+    async fn read_vec(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut buf = [0u8; 1024];
+
+        loop {
+            match self.read_with_timeout(&mut buf).await {
+                Ok(0) => return Ok(data),
+                Ok(n) => data.extend_from_slice(&buf[..n]),
+                Err(e) if e.kind() == io::ErrorKind::TimedOut && !data.is_empty() => {
+                    return Ok(data)
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        Ok(self.stream.write_all(data).await?)
+    }
+
+    async fn flush(&mut self) -> Result<()> {
+        Ok(self.stream.flush().await?)
+    }
+}
+
+/// The emulator never blocks, it can be used as either interface
+impl AsyncPTouchInterface for Emulator {
+    fn name(&self) -> String {
+        PTouchInterface::name(self)
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        PTouchInterface::read(self, buf)
+    }
+
+    async fn read_vec(&mut self) -> Result<Vec<u8>> {
+        PTouchInterface::read_vec(self)
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        PTouchInterface::write(self, data)
+    }
+}
+
+#[cfg(test)]
+impl AsyncPTouchInterface for crate::interface::TestInterface {
+    fn name(&self) -> String {
+        PTouchInterface::name(self)
+    }
+
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        PTouchInterface::read(self, buf)
+    }
+
+    async fn read_vec(&mut self) -> Result<Vec<u8>> {
+        PTouchInterface::read_vec(self)
+    }
+
+    async fn write(&mut self, data: &[u8]) -> Result<()> {
+        PTouchInterface::write(self, data)
+    }
+}
+
+/// Async counterpart of `Commands`
+pub trait AsyncCommands {
+    /// Null command
+    fn null(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Init command, sets up the device for printing
+    fn init(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Invalidate command, resets the device
+    fn invalidate(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Issue a status request
+    fn status_req(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Read a status response, bounded by the read timeout of the interface
+    fn read_status(&mut self) -> impl Future<Output = Result<Status>> + Send;
+
+    /// Set status notify (printer automatically sends status on change)
+    fn set_status_notify(&mut self, enabled: bool) -> impl Future<Output = Result<()>> + Send;
+
+    /// Switch mode, required for raster printing
+    fn switch_mode(&mut self, mode: Mode) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set print information
+    fn set_print_info(&mut self, info: &PrintInfo) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set various mode flags
+    fn set_various_mode(&mut self, mode: VariousMode) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set advanced mode flags
+    fn set_advanced_mode(&mut self, mode: AdvancedMode) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set pre/post print margin
+    fn set_margin(&mut self, dots: u16) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set print page number
+    fn set_page_no(&mut self, no: u8) -> impl Future<Output = Result<()>> + Send;
+
+    /// Set compression mode (None or Tiff), see `Commands::set_compression_mode`
+    fn set_compression_mode(
+        &mut self,
+        mode: CompressionMode,
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Transfer (uncompressed) raster data, see `Commands::transfer_raster_line`
+    fn transfer_raster_line(&mut self, data: &[u8]) -> impl Future<Output = Result<()>> + Send;
+
+    /// Transfer a two-colour raster line, see `Commands::transfer_two_colour_raster_line`
+    fn transfer_two_colour_raster_line(
+        &mut self,
+        black: &[u8],
+        red: &[u8],
+    ) -> impl Future<Output = Result<()>> + Send;
+
+    /// Send a zero raster line (TIFF mode only)
+    fn raster_zero(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Start a print
+    fn print(&mut self) -> impl Future<Output = Result<()>> + Send;
+
+    /// Start a print and feed
+    fn print_and_feed(&mut self) -> impl Future<Output = Result<()>> + Send;
+}
+
+/// Async counterpart of `GetStatus`
+pub trait AsyncGetStatus {
+    fn get_status(&mut self) -> impl Future<Output = Result<Status>> + Send;
+}
+
+/// Async counterpart of `PTouchPrinter`
+pub struct AsyncPTouchPrinter<D> {
+    pub interface: D,
+    protocol: Protocol,
+}
+
+/// Connect to a network printer
+pub async fn from_addr<A: ToSocketAddrs>(
+    addr: A,
+) -> Result<AsyncPTouchPrinter<AsyncPTouchTcpInterface>> {
+    Ok(AsyncPTouchPrinter::from_interface(
+        AsyncPTouchTcpInterface::new(addr, Some(DEFAULT_TIMEOUT)).await?,
+    ))
+}
+
+/// Like `from_addr`, with buffered IO: a job goes out in a single write on `flush`
+pub async fn from_addr_buffered<A: ToSocketAddrs>(
+    addr: A,
+) -> Result<AsyncPTouchPrinter<AsyncPTouchTcpInterface>> {
+    Ok(AsyncPTouchPrinter::buffered(
+        AsyncPTouchTcpInterface::new(addr, Some(DEFAULT_TIMEOUT)).await?,
+        DEFAULT_BUFFER_LIMIT,
+    ))
+}
+
+impl<D: AsyncPTouchInterface> AsyncPTouchPrinter<D> {
+    /// Direct IO, every command is written to the interface right away
+    pub fn from_interface(interface: D) -> Self {
+        Self {
+            interface,
+            protocol: Protocol::new(None),
+        }
+    }
+
+    /// Buffered IO, commands are collected until `flush` (or until `limit` bytes are waiting)
+    pub fn buffered(interface: D, limit: usize) -> Self {
+        Self {
+            interface,
+            protocol: Protocol::new(Some(limit)),
+        }
+    }
+
+    pub fn is_buffered(&self) -> bool {
+        self.protocol.is_buffered()
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.protocol.engine()
+    }
+
+    /// Select the print engine, see `PTouchPrinter::set_engine`
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.protocol.set_engine(engine);
+    }
+
+    /// Select the print engine and capabilities of `model`, unknown models leave both unchanged
+    pub fn set_model(&mut self, model: Model) {
+        self.protocol.set_model(model);
+    }
+
+    /// Capabilities of the printer, known after a status read or `set_model`
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.protocol.capabilities()
+    }
+
+    /// Send a single command, tracking the compression mode of the printer
+    pub async fn send(&mut self, command: &Command) -> Result<()> {
+        let data = self.protocol.encode(command);
+        self.write(data).await
+    }
+
+    /// Send raw bytes, or add them to the send buffer. A full buffer is written out first
+    pub async fn write(&mut self, data: impl AsRef<[u8]>) -> Result<()> {
+        match self.protocol.queue(data.as_ref()) {
+            Some(due) => self.interface.write(&due).await,
+            None => Ok(()),
+        }
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        if let Some(buffered) = self.protocol.take_buffered() {
+            self.interface.write(&buffered).await?;
+        }
+
+        self.interface.flush().await
+    }
+
+    /// Send a complete print job to the printer, without validation, see `PTouchPrinter::print_job`
+    pub async fn print_job(&mut self, job: &PrintJob) -> Result<()> {
+        for command in &self.protocol.job_commands(job) {
+            self.send(command).await?;
+        }
+
+        self.flush().await
+    }
+
+    /// Read the status and check the job fits, see `PTouchPrinter::preflight`
+    pub async fn preflight(&mut self, job: &PrintJob) -> Result<Status> {
+        let status = self.get_status().await?;
+        job.check(&status)?;
+
+        Ok(status)
+    }
+
+    /// Check the job against the printer, send it and wait for the printer to finish it
+    pub async fn print_job_and_wait(
+        &mut self,
+        job: &PrintJob,
+        timeout: Duration,
+    ) -> Result<JobOutcome> {
+        self.preflight(job).await?;
+        self.print_job(job).await?;
+        self.wait_for_job(job, timeout).await
+    }
+
+    /// Follow the status notifications until every page of `job` is printed or the printer
+    /// reports an error, see `PTouchPrinter::wait_for_job`
+    pub async fn wait_for_job(&mut self, job: &PrintJob, timeout: Duration) -> Result<JobOutcome> {
+        let deadline = Instant::now() + timeout;
+        let mut tracker = NotificationTracker::default();
+        if let Some(outcome) = tracker.outcome(job, self.capabilities().as_ref()) {
+            return Ok(outcome);
+        }
+
+        loop {
+            // The read may fail right away, before its timeout gets a chance to elapse
+            if Instant::now() >= deadline {
+                return Err(PTouchError::Timeout);
+            }
+
+            // Cancelling the read at the deadline is safe, a partly read status frame is kept
+            let status = match time::timeout_at(deadline, self.read_status()).await {
+                Ok(Ok(status)) => status,
+                // Nothing to report yet
+                Ok(Err(PTouchError::IoError(e)))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    time::sleep_until(deadline.min(Instant::now() + job::POLL_INTERVAL)).await;
+                    continue;
+                }
+                Ok(Err(e)) => return Err(e),
+                Err(_elapsed) => return Err(PTouchError::Timeout),
+            };
+
+            if let Some(outcome) = tracker.update(job, &status) {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+impl AsyncPTouchPrinter<AsyncPTouchTcpInterface> {
+    /// SNMP client for the printer, using the `SnmpSettings` of the interface
+    pub fn snmp_monitor(&self) -> AsyncSnmpMonitor {
+        AsyncSnmpMonitor::with_settings(self.interface.ip_addr, self.interface.snmp.clone())
+    }
+
+    pub async fn get_snmp_status(&self) -> Result<Status> {
+        self.snmp_monitor().get_status().await
+    }
+
+    /// Like `print_job_and_wait`, polling the status and page counter over SNMP, see
+    /// `SnmpMonitor::wait_for_job`
+    pub async fn print_job_and_wait_snmp(
+        &mut self,
+        job: &PrintJob,
+        timeout: Duration,
+    ) -> Result<JobOutcome> {
+        job::check_snmp(&self.preflight(job).await?)?;
+        let monitor = self.snmp_monitor();
+        let start_count = monitor.get_page_count().await?;
+        self.print_job(job).await?;
+        monitor.wait_for_job(job, start_count, timeout).await
+    }
+}
+
+impl<D: AsyncPTouchInterface> AsyncCommands for AsyncPTouchPrinter<D> {
+    async fn null(&mut self) -> Result<()> {
+        self.send(&Command::Invalidate(1)).await
+    }
+
+    async fn init(&mut self) -> Result<()> {
+        self.send(&Command::Init).await
+    }
+
+    async fn invalidate(&mut self) -> Result<()> {
+        self.send(&Command::Invalidate(400)).await
+    }
+
+    async fn status_req(&mut self) -> Result<()> {
+        self.send(&Command::StatusRequest).await
+    }
+
+    async fn read_status(&mut self) -> Result<Status> {
+        loop {
+            let n = self.interface.read(self.protocol.status_buf()).await?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+
+            if let Some(status) = self.protocol.status_read(n) {
+                return status;
+            }
+        }
+    }
+
+    async fn set_status_notify(&mut self, enabled: bool) -> Result<()> {
+        self.send(&Command::StatusNotify(enabled)).await
+    }
+
+    async fn switch_mode(&mut self, mode: Mode) -> Result<()> {
+        self.send(&Command::SwitchMode(mode)).await
+    }
+
+    async fn set_print_info(&mut self, info: &PrintInfo) -> Result<()> {
+        self.send(&Command::PrintInfo(info.clone())).await
+    }
+
+    async fn set_various_mode(&mut self, mode: VariousMode) -> Result<()> {
+        self.send(&Command::VariousMode(mode)).await
+    }
+
+    async fn set_advanced_mode(&mut self, mode: AdvancedMode) -> Result<()> {
+        self.send(&Command::AdvancedMode(mode)).await
+    }
+
+    async fn set_margin(&mut self, dots: u16) -> Result<()> {
+        self.send(&Command::Margin(dots)).await
+    }
+
+    async fn set_page_no(&mut self, no: u8) -> Result<()> {
+        self.send(&Command::PageNo(no)).await
+    }
+
+    async fn set_compression_mode(&mut self, mode: CompressionMode) -> Result<()> {
+        let command = self.protocol.compression_mode(mode);
+        self.send(&command).await
+    }
+
+    async fn transfer_raster_line(&mut self, data: &[u8]) -> Result<()> {
+        let command = self.protocol.raster_line(data);
+        self.send(&command).await
+    }
+
+    async fn transfer_two_colour_raster_line(&mut self, black: &[u8], red: &[u8]) -> Result<()> {
+        for command in self.protocol.two_colour_raster_line(black, red) {
+            self.send(&command).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn raster_zero(&mut self) -> Result<()> {
+        self.send(&Command::RasterZero).await
+    }
+
+    async fn print(&mut self) -> Result<()> {
+        self.send(&Command::Print).await
+    }
+
+    async fn print_and_feed(&mut self) -> Result<()> {
+        self.send(&Command::PrintAndFeed).await
+    }
+}
+
+/// Status over the data channel, pending notifications are skipped
+impl<D: AsyncPTouchInterface> AsyncGetStatus for AsyncPTouchPrinter<D> {
+    async fn get_status(&mut self) -> Result<Status> {
+        self.status_req().await?;
+        self.flush().await?;
+
+        loop {
+            let status = self.read_status().await?;
+            if status.status_type == DeviceStatus::Reply {
+                return Ok(status);
+            }
+        }
+    }
+}
+
+/// Async counterpart of `SnmpMonitor`
+#[derive(Clone, PartialEq, Debug)]
+pub struct AsyncSnmpMonitor {
+    ip_addr: IpAddr,
+    settings: SnmpSettings,
+}
+
+impl AsyncSnmpMonitor {
+    /// Monitor the printer at `ip_addr`, with the default `SnmpSettings`
+    pub fn new(ip_addr: IpAddr) -> Self {
+        Self::with_settings(ip_addr, SnmpSettings::default())
+    }
+
+    pub fn with_settings(ip_addr: IpAddr, settings: SnmpSettings) -> Self {
+        Self { ip_addr, settings }
+    }
+
+    pub fn ip_addr(&self) -> IpAddr {
+        self.ip_addr
+    }
+
+    pub fn settings(&self) -> &SnmpSettings {
+        &self.settings
+    }
+
+    pub async fn get_status(&self) -> Result<Status> {
+        self.get_value(snmp_oid::STATUS).await?.into_status()
+    }
+
+    pub async fn get_name(&self) -> Result<String> {
+        self.get_value(snmp_oid::NAME).await?.into_string()
+    }
+
+    pub async fn get_model(&self) -> Result<String> {
+        self.get_value(snmp_oid::MODEL).await?.into_string()
+    }
+
+    pub async fn get_serial(&self) -> Result<String> {
+        self.get_value(snmp_oid::SERIAL).await?.into_string()
+    }
+
+    pub async fn get_ip_addr(&self) -> Result<Ipv4Addr> {
+        self.get_value(snmp_oid::IP_ADDR).await?.into_ipv4()
+    }
+
+    pub async fn get_subnet(&self) -> Result<Ipv4Addr> {
+        self.get_value(snmp_oid::SUBNET).await?.into_ipv4()
+    }
+
+    pub async fn get_mac(&self) -> Result<[u8; 6]> {
+        self.get_value(snmp_oid::MAC).await?.into_mac()
+    }
+
+    /// Time since the network interface was started
+    pub async fn get_uptime(&self) -> Result<Duration> {
+        self.get_value(snmp_oid::UPTIME).await?.into_duration()
+    }
+
+    /// Pages printed over the lifetime of the printer
+    pub async fn get_page_count(&self) -> Result<u64> {
+        self.get_value(snmp_oid::LIFE_COUNT).await?.into_counter()
+    }
+
+    /// Pages printed since the printer was turned on
+    pub async fn get_power_on_page_count(&self) -> Result<u64> {
+        self.get_value(snmp_oid::POWER_ON_COUNT)
+            .await?
+            .into_counter()
+    }
+
+    /// Printer state as reported by the standard MIB, see `get_status` for the details
+    pub async fn get_printer_state(&self) -> Result<PrinterState> {
+        self.get_value(snmp_oid::PRINTER_STATUS)
+            .await?
+            .into_printer_state()
+    }
+
+    /// Query a single OID, see `SnmpMonitor::get_value`
+    pub async fn get_value(&self, oid: &[u32]) -> Result<SnmpValue> {
+        static REQUEST_ID: AtomicI32 = AtomicI32::new(1);
+
+        let settings = &self.settings;
+        let local_addr = match self.ip_addr {
+            IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let socket = UdpSocket::bind(SocketAddr::new(local_addr, 0)).await?;
+        socket.connect((self.ip_addr, settings.port)).await?;
+
+        let req_id = REQUEST_ID.fetch_add(1, Ordering::Relaxed) & i32::MAX;
+        let request = {
+            let mut buf = pdu::Buf::default();
+            pdu::build_get(settings.community.as_bytes(), req_id, oid, &mut buf);
+            buf.to_vec()
+        };
+
+        let mut buf = [0u8; 4096];
+        let mut error = String::from("Timeout");
+        for _attempt in 0..=settings.retries {
+            socket.send(&request).await?;
+            let deadline = Instant::now() + settings.timeout;
+
+            // Skip stray responses, e.g. late ones to an earlier attempt
+            loop {
+                let n = match time::timeout_at(deadline, socket.recv(&mut buf)).await {
+                    Ok(Ok(n)) => n,
+                    Ok(Err(e)) => {
+                        error = format!("{e:?}");
+                        break;
+                    }
+                    Err(_elapsed) => break,
+                };
+
+                let Ok(mut response) = SnmpPdu::from_bytes(&buf[..n]) else {
+                    continue;
+                };
+                if response.message_type != SnmpMessageType::Response || response.req_id != req_id {
+                    continue;
+                }
+
+                return response
+                    .varbinds
+                    .next()
+                    .and_then(|(_oid, value)| SnmpValue::from_varbind(value))
+                    .ok_or_else(|| SnmpError::UnexpectedValue.into());
+            }
+        }
+
+        Err(SnmpError::Request(error).into())
+    }
+
+    /// Poll the status and page counter until the job is done, see `SnmpMonitor::wait_for_job`
+    pub async fn wait_for_job(
+        &self,
+        job: &PrintJob,
+        start_count: u64,
+        timeout: Duration,
+    ) -> Result<JobOutcome> {
+        let deadline = Instant::now() + timeout;
+        let tracker = JobTracker::new(start_count);
+
+        loop {
+            let status = self.get_status().await?;
+            let page_count = self.get_page_count().await?;
+            if let Some(outcome) = tracker.update(job, &status, page_count) {
+                return Ok(outcome);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(PTouchError::Timeout);
+            }
+            time::sleep_until(deadline.min(now + job::POLL_INTERVAL)).await;
+        }
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn async_print_job() {
+    use crate::{media, prelude::*, status::ErrorStatus};
+
+    let media = media::by_product("DK-22205").unwrap();
+    let job = PrintJob::new()
+        .media(media)
+        .page(RasterBuffer::new(10))
+        .copies(2)
+        .compression(CompressionMode::Tiff);
+    let timeout = Duration::from_secs(1);
+
+    let mut printer =
+        AsyncPTouchPrinter::from_interface(Emulator::new(Model::QL820NWB, Some(media)));
+    assert_eq!(printer.get_status().await.unwrap().model, Model::QL820NWB);
+    assert_eq!(printer.engine(), Engine::DeskLabel);
+    assert_eq!(
+        printer.print_job_and_wait(&job, timeout).await.unwrap(),
+        JobOutcome::Cut
+    );
+    assert_eq!(printer.interface.take_pages().len(), 2);
+
+    // Same command stream as the sync printer
+    let mut expected = PTouchPrinter::from_interface(crate::interface::TestInterface::default());
+    expected.set_model(Model::QL820NWB);
+    expected.print_job(&job).unwrap();
+    let mut printer = AsyncPTouchPrinter::buffered(crate::interface::TestInterface::default(), 100);
+    printer.set_model(Model::QL820NWB);
+    printer.print_job(&job).await.unwrap();
+    assert_eq!(printer.interface.sent, expected.interface.sent);
+
+    let mut printer =
+        AsyncPTouchPrinter::from_interface(Emulator::new(Model::QL820NWB, Some(media)));
+    printer.interface.set_error(ErrorStatus::COVER_OPEN);
+    match printer.print_job_and_wait(&job, timeout).await {
+        Err(PTouchError::Printer(ErrorStatus::COVER_OPEN)) => {}
+        r => panic!("Unexpected {r:?}"),
+    }
+    match printer.wait_for_job(&job, Duration::from_millis(200)).await {
+        Err(PTouchError::Timeout) => {}
+        r => panic!("Unexpected {r:?}"),
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn async_tcp_and_snmp() {
+    use crate::{emulator::EmulatorServer, media, prelude::*};
+
+    let media = media::by_product("DK-11201").unwrap();
+    let server =
+        EmulatorServer::bind("127.0.0.1:0", Emulator::new(Model::QL720NW, Some(media))).unwrap();
+    let agent = server.serve_snmp("127.0.0.1:0").unwrap();
+
+    let mut printer = from_addr_buffered(server.local_addr()).await.unwrap();
+    printer.interface.set_snmp_settings(SnmpSettings {
+        port: agent.local_addr().port(),
+        ..SnmpSettings::default()
+    });
+
+    let status = printer.get_status().await.unwrap();
+    assert_eq!((status.media_width, status.media_length), (29, 90));
+
+    let job = PrintJob::new().media(media).page(RasterBuffer::new(991));
+    printer.print_job(&job).await.unwrap();
+    assert_eq!(
+        printer
+            .wait_for_job(&job, Duration::from_secs(2))
+            .await
+            .unwrap(),
+        JobOutcome::Cut
+    );
+    assert_eq!(server.emulator().pages().len(), 1);
+
+    let monitor = printer.snmp_monitor();
+    assert_eq!(monitor.get_model().await.unwrap(), "Brother QL-720NW");
+    assert_eq!(
+        printer.get_snmp_status().await.unwrap().model,
+        Model::QL720NW
+    );
+    assert_eq!(
+        monitor.get_value(snmp_oid::LIFE_COUNT).await.unwrap(),
+        SnmpValue::Counter(1)
+    );
+    assert_eq!(monitor.get_page_count().await.unwrap(), 1);
+    assert_eq!(monitor.get_power_on_page_count().await.unwrap(), 1);
+    assert_eq!(monitor.get_ip_addr().await.unwrap(), Ipv4Addr::LOCALHOST);
+    assert_eq!(
+        monitor.get_subnet().await.unwrap(),
+        Ipv4Addr::new(255, 255, 255, 0)
+    );
+    let [port_hi, port_lo] = agent.local_addr().port().to_be_bytes();
+    assert_eq!(
+        monitor.get_mac().await.unwrap(),
+        [0x00, 0x80, 0x92, 0x00, port_hi, port_lo]
+    );
+    assert!(monitor.get_uptime().await.unwrap() < Duration::from_secs(60));
+    assert_eq!(
+        monitor.get_printer_state().await.unwrap(),
+        PrinterState::Idle
+    );
+
+    // Not answered by the agent
+    match monitor.get_value(&[1, 3, 6, 1, 2, 1, 1, 1, 0]).await {
+        Err(PTouchError::SNMPError(SnmpError::UnexpectedValue)) => {}
+        r => panic!("Unexpected {r:?}"),
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn cancelled_status_read() {
+    use tokio::net::TcpListener;
+
+    let mut emulator = Emulator::new(Model::QL720NW, None);
+    PTouchInterface::write(&mut emulator, &Command::StatusRequest.encode()).unwrap();
+    let frame = PTouchInterface::read_vec(&mut emulator).unwrap();
+
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (interface, accepted) =
+        tokio::join!(AsyncPTouchTcpInterface::new(addr, None), listener.accept());
+    let mut printer = AsyncPTouchPrinter::from_interface(interface.unwrap());
+    let (mut peer, _) = accepted.unwrap();
+
+    // Cancelled halfway through the frame, the rest completes it on the next read
+    peer.write_all(&frame[..10]).await.unwrap();
+    let read = time::timeout(Duration::from_millis(50), printer.read_status()).await;
+    assert!(read.is_err());
+
+    peer.write_all(&frame[10..]).await.unwrap();
+    let status = time::timeout(Duration::from_secs(1), printer.read_status())
+        .await
+        .expect("Status frame lost")
+        .unwrap();
+    assert_eq!(status.model, Model::QL720NW);
+    assert_eq!(printer.engine(), Engine::DeskLabel);
+}
@@ -0,0 +1,571 @@
+//! Command line tool to print images, query printers and convert images to `.prn` files.
+//!
+//! Run `ql-raster help` for usage. The printer is selected with `-p`/`--printer` or the
+//! `QL_PRINTER` environment variable, e.g. `labelprinter:9100`, `usb:/dev/usb/lp0` or
+//! `serial:/dev/rfcomm0`.
+
+use std::{
+    env,
+    error::Error,
+    net::{Ipv4Addr, ToSocketAddrs},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    time::Duration,
+};
+
+use image::DynamicImage;
+use ql_raster::{
+    commands::CompressionMode,
+    discovery::Discovery,
+    emulator::Emulator,
+    job::{CutMode, JobOutcome, Page, PrintJob},
+    media,
+    monitor::SnmpMonitor,
+    prelude::*,
+    rasterize::{Align, Dither, Rasterizer},
+    status::Model,
+};
+
+#[cfg(unix)]
+use ql_raster::interface::SerialSettings;
+
+type CliResult<T> = std::result::Result<T, Box<dyn Error>>;
+
+const USAGE: &str = "\
+Usage: ql-raster [-p PRINTER] <COMMAND> [OPTIONS]
+
+Commands:
+  print <IMAGE>...    Print images, one label per image
+  status              Show the printer status (--snmp: without using the print port)
+  info                Show name, model and serial number (network printers, SNMP)
+  convert <IMAGE>...  Convert images to a print job file (-o FILE.prn), without a printer
+  preview <IMAGE>...  Render the labels as printed (-o FILE.png), without a printer
+  discover            Search the network for printers (SNMP broadcast)
+  media               List the known media
+  help                Show this help
+
+Printer (-p, --printer, or the QL_PRINTER environment variable):
+  HOST[:PORT]         Network printer, port 9100 by default
+  usb:PATH            USB printer class device, e.g. usb:/dev/usb/lp0
+  serial:PATH         Serial or Bluetooth tty, e.g. serial:/dev/rfcomm0
+
+Options:
+  -m, --media CODE    Media by product code, e.g. DK-22205 (default: as loaded in the printer)
+  -o, --output FILE   Output file of convert and preview
+      --model MODEL   Printer model for convert and preview, e.g. QL800
+  -n, --copies N      Number of copies
+      --dither ALGO   otsu, floyd-steinberg, atkinson, bayer or a threshold 0-255
+      --invert        Print light pixels instead of dark ones
+      --align ALIGN   left, centre or right
+      --high-res      600dpi print quality, on supported models
+      --no-cut        Do not cut the labels
+      --cut-every N   Cut after every N labels
+      --no-wait       Do not wait for the printer to finish the job
+      --timeout SECS  Time to wait for the printer to finish (default 60), or for
+                      discovery answers (default 2)
+      --subnet NET    Subnet to search, e.g. 192.168.1.0/24 (default: local broadcast)
+      --mdns          Also search for printers advertised over mDNS
+      --snmp          Query the status over SNMP";
+
+fn main() -> ExitCode {
+    match run(env::args().skip(1).collect()) {
+        Ok(code) => code,
+        Err(e) => {
+            eprintln!("ql-raster: {e}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(args: Vec<String>) -> CliResult<ExitCode> {
+    let options = Options::parse(args)?;
+
+    match options.command.as_str() {
+        "print" => print(&options),
+        "status" => status(&options),
+        "info" => info(&options),
+        "convert" => convert(&options),
+        "preview" => preview(&options),
+        "discover" => discover(&options),
+        "media" => list_media(),
+        "help" => {
+            println!("{USAGE}");
+            Ok(ExitCode::SUCCESS)
+        }
+        command => Err(format!("unknown command '{command}', see 'ql-raster help'").into()),
+    }
+}
+
+/// Parsed command line
+struct Options {
+    command: String,
+    images: Vec<PathBuf>,
+    printer: Option<String>,
+    media: Option<&'static Media>,
+    model: Option<Model>,
+    output: Option<PathBuf>,
+    copies: u16,
+    dither: Dither,
+    invert: bool,
+    align: Align,
+    high_res: bool,
+    cut: CutMode,
+    wait: bool,
+    timeout: Option<Duration>,
+    subnet: Option<(Ipv4Addr, u8)>,
+    mdns: bool,
+    snmp: bool,
+}
+
+impl Options {
+    fn parse(args: Vec<String>) -> CliResult<Self> {
+        let mut options = Options {
+            command: String::new(),
+            images: Vec::new(),
+            printer: env::var("QL_PRINTER").ok(),
+            media: None,
+            model: None,
+            output: None,
+            copies: 1,
+            dither: Dither::default(),
+            invert: false,
+            align: Align::default(),
+            high_res: false,
+            cut: CutMode::default(),
+            wait: true,
+            timeout: None,
+            subnet: None,
+            mdns: false,
+            snmp: false,
+        };
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = |name: &str| {
+                args.next()
+                    .ok_or_else(|| format!("missing value for {name}"))
+            };
+
+            match arg.as_str() {
+                "-p" | "--printer" => options.printer = Some(value(&arg)?),
+                "-m" | "--media" => {
+                    let code = value(&arg)?;
+                    let media = media::by_product(&code.to_uppercase())
+                        .ok_or_else(|| format!("unknown media '{code}', see 'ql-raster media'"))?;
+                    options.media = Some(media);
+                }
+                "--model" => options.model = Some(parse_model(&value(&arg)?)?),
+                "-o" | "--output" => options.output = Some(value(&arg)?.into()),
+                "-n" | "--copies" => options.copies = parse_number(&arg, &value(&arg)?)?,
+                "--dither" => options.dither = parse_dither(&value(&arg)?)?,
+                "--invert" => options.invert = true,
+                "--align" => options.align = parse_align(&value(&arg)?)?,
+                "--high-res" => options.high_res = true,
+                "--no-cut" => options.cut = CutMode::None,
+                "--cut-every" => options.cut = CutMode::Every(parse_number(&arg, &value(&arg)?)?),
+                "--no-wait" => options.wait = false,
+                "--subnet" => options.subnet = Some(parse_subnet(&value(&arg)?)?),
+                "--mdns" => options.mdns = true,
+                "--snmp" => options.snmp = true,
+                "--timeout" => {
+                    options.timeout = Some(Duration::from_secs(parse_number(&arg, &value(&arg)?)?))
+                }
+                "-h" | "--help" => options.command = "help".into(),
+                _ if arg.starts_with('-') => return Err(format!("unknown option '{arg}'").into()),
+                _ if options.command.is_empty() => options.command = arg,
+                _ => options.images.push(arg.into()),
+            }
+        }
+
+        if options.command.is_empty() {
+            options.command = "help".into();
+        }
+
+        Ok(options)
+    }
+
+    fn printer_spec(&self) -> CliResult<&str> {
+        self.printer
+            .as_deref()
+            .ok_or_else(|| "no printer given, use -p or set QL_PRINTER".into())
+    }
+
+    fn output(&self) -> CliResult<&Path> {
+        self.output
+            .as_deref()
+            .ok_or_else(|| format!("{} needs an output file (-o)", self.command).into())
+    }
+
+    fn media_or_err(&self) -> CliResult<&'static Media> {
+        self.media
+            .ok_or_else(|| format!("{} needs the media (-m), e.g. DK-22205", self.command).into())
+    }
+
+    /// Rasterize the images, one page each
+    fn job(&self, media: &Media) -> CliResult<PrintJob> {
+        if self.images.is_empty() {
+            return Err(format!("{} needs at least one image", self.command).into());
+        }
+
+        let rasterizer = Rasterizer::new(media)
+            .dither(self.dither)
+            .invert(self.invert)
+            .align(self.align);
+
+        let mut job = PrintJob::new()
+            .media(media)
+            .copies(self.copies)
+            .cut(self.cut)
+            .cut_at_end(self.cut != CutMode::None)
+            .high_res(self.high_res)
+            .compression(CompressionMode::Tiff);
+
+        for path in &self.images {
+            let image = image::open(path).map_err(|e| format!("{}: {e}", path.display()))?;
+            job = job.page(page(&rasterizer, media, &image));
+        }
+
+        Ok(job)
+    }
+
+    /// Model given with `--model`, or a model using the print engine of the media
+    fn model_for(&self, media: &Media) -> Model {
+        self.model.unwrap_or(match media.engine {
+            Engine::HandHeld => Model::PTP750W,
+            Engine::HandHeldWide => Model::PTP900W,
+            Engine::DeskLabel => Model::QL820NWB,
+            Engine::DeskLabelWide => Model::QL1110NWB,
+        })
+    }
+}
+
+fn page(rasterizer: &Rasterizer, media: &Media, image: &DynamicImage) -> Page {
+    if media.two_colour {
+        rasterizer.rasterize_two_colour(image).into()
+    } else {
+        rasterizer.rasterize(image).into()
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(name: &str, value: &str) -> CliResult<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for {name}").into())
+}
+
+fn parse_model(name: &str) -> CliResult<Model> {
+    let name = name.replace('-', "");
+
+    (0..=u8::MAX)
+        .map(Model::from)
+        .filter(|model| !matches!(model, Model::Unknown(_)))
+        .find(|model| format!("{model:?}").eq_ignore_ascii_case(&name))
+        .ok_or_else(|| format!("unknown model '{name}'").into())
+}
+
+fn parse_dither(name: &str) -> CliResult<Dither> {
+    Ok(match name.to_lowercase().as_str() {
+        "otsu" => Dither::Otsu,
+        "floyd-steinberg" | "floyd" => Dither::FloydSteinberg,
+        "atkinson" => Dither::Atkinson,
+        "bayer" => Dither::Bayer,
+        threshold => Dither::Threshold(parse_number("--dither", threshold)?),
+    })
+}
+
+fn parse_subnet(subnet: &str) -> CliResult<(Ipv4Addr, u8)> {
+    let invalid = || format!("invalid subnet '{subnet}', e.g. 192.168.1.0/24");
+    let (addr, prefix_len) = subnet.split_once('/').unwrap_or((subnet, "24"));
+
+    let addr = addr.parse().map_err(|_| invalid())?;
+    match prefix_len.parse() {
+        Ok(prefix_len @ 0..=32) => Ok((addr, prefix_len)),
+        _ => Err(invalid().into()),
+    }
+}
+
+fn parse_align(name: &str) -> CliResult<Align> {
+    Ok(match name.to_lowercase().as_str() {
+        "left" => Align::Left,
+        "centre" | "center" => Align::Centre,
+        "right" => Align::Right,
+        _ => return Err(format!("invalid alignment '{name}'").into()),
+    })
+}
+
+/// Any of the printer interfaces, selected at runtime
+enum AnyInterface {
+    Tcp(PTouchTcpInterface),
+    #[cfg(unix)]
+    UsbLp(PTouchUsbLpInterface),
+    #[cfg(unix)]
+    Serial(PTouchSerialInterface),
+}
+
+macro_rules! forward {
+    ($self:ident, $interface:ident => $call:expr) => {
+        match $self {
+            AnyInterface::Tcp($interface) => $call,
+            #[cfg(unix)]
+            AnyInterface::UsbLp($interface) => $call,
+            #[cfg(unix)]
+            AnyInterface::Serial($interface) => $call,
+        }
+    };
+}
+
+impl PTouchInterface for AnyInterface {
+    fn name(&self) -> String {
+        forward!(self, interface => interface.name())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        forward!(self, interface => interface.read(buf))
+    }
+
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        forward!(self, interface => interface.read_vec())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        forward!(self, interface => interface.write(data))
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        forward!(self, interface => interface.flush())
+    }
+}
+
+/// Network address of the printer, `None` for local interfaces
+fn network_addr(spec: &str) -> Option<String> {
+    if spec.starts_with("usb:") || spec.starts_with("serial:") {
+        None
+    } else if spec.contains(':') {
+        Some(spec.to_string())
+    } else {
+        Some(format!("{spec}:9100"))
+    }
+}
+
+fn open_printer(options: &Options) -> CliResult<PTouchPrinter<AnyInterface>> {
+    let spec = options.printer_spec()?;
+
+    let interface = if let Some(addr) = network_addr(spec) {
+        AnyInterface::Tcp(printer::from_addr_buffered(addr)?.interface)
+    } else {
+        open_local(spec)?
+    };
+
+    Ok(PTouchPrinter::buffered(
+        interface,
+        printer::DEFAULT_BUFFER_LIMIT,
+    ))
+}
+
+#[cfg(unix)]
+fn open_local(spec: &str) -> CliResult<AnyInterface> {
+    Ok(match spec.split_once(':') {
+        Some(("usb", path)) => AnyInterface::UsbLp(printer::from_usb_lp(path)?.interface),
+        Some(("serial", path)) => {
+            AnyInterface::Serial(printer::from_serial(path, &SerialSettings::default())?.interface)
+        }
+        _ => return Err(format!("invalid printer '{spec}'").into()),
+    })
+}
+
+#[cfg(not(unix))]
+fn open_local(spec: &str) -> CliResult<AnyInterface> {
+    Err(format!("printer '{spec}' is not supported on this platform").into())
+}
+
+/// SNMP client for a network printer, leaves the print port free for others
+fn open_monitor(options: &Options) -> CliResult<SnmpMonitor> {
+    let addr = network_addr(options.printer_spec()?)
+        .ok_or_else(|| format!("{} needs a network printer", options.command))?;
+    let ip_addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| format!("unknown host '{addr}'"))?
+        .ip();
+
+    Ok(SnmpMonitor::new(ip_addr))
+}
+
+fn print(options: &Options) -> CliResult<ExitCode> {
+    let mut printer = open_printer(options)?;
+    let status = printer.get_status()?;
+    let media = match options.media {
+        Some(media) => media,
+        None => Media::from_status(&status).ok_or("unknown media loaded, select it with -m")?,
+    };
+
+    let job = options.job(media)?;
+    job.check(&status)?;
+    printer.print_job(&job)?;
+    if !options.wait {
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let timeout = options.timeout.unwrap_or(Duration::from_secs(60));
+    match printer.wait_for_job(&job, timeout)? {
+        JobOutcome::Failed(error_status) => {
+            eprintln!("ql-raster: printing failed: {error_status}");
+            Ok(ExitCode::FAILURE)
+        }
+        _ => Ok(ExitCode::SUCCESS),
+    }
+}
+
+fn status(options: &Options) -> CliResult<ExitCode> {
+    let status = match options.snmp {
+        true => open_monitor(options)?.get_status()?,
+        false => open_printer(options)?.get_status()?,
+    };
+
+    println!("Model:  {:?}", status.model);
+    match Media::from_status(&status) {
+        Some(media) => println!("Media:  {media} {}", media.product.unwrap_or_default()),
+        None => println!(
+            "Media:  {:?} {}x{}mm",
+            status.media_kind, status.media_width, status.media_length
+        ),
+    }
+    println!("Phase:  {:?}", status.phase);
+    println!("Errors: {}", status.error_status);
+
+    Ok(if status.error_status.is_empty() {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn info(options: &Options) -> CliResult<ExitCode> {
+    let monitor = open_monitor(options)?;
+
+    println!("Name:   {}", monitor.get_name()?);
+    println!("Model:  {}", monitor.get_model()?);
+    println!("Serial: {}", monitor.get_serial()?);
+
+    // Not every firmware has these
+    let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".into());
+    let ip_addr = monitor.get_ip_addr().ok().map(|ip| ip.to_string());
+    let subnet = monitor.get_subnet().ok().map(|ip| ip.to_string());
+    let mac = monitor.get_mac().ok().map(|mac| {
+        let octets: Vec<_> = mac.iter().map(|octet| format!("{octet:02X}")).collect();
+        octets.join(":")
+    });
+    let pages = monitor.get_page_count().ok().map(|n| n.to_string());
+    let uptime = monitor
+        .get_uptime()
+        .ok()
+        .map(|uptime| format!("{}s", uptime.as_secs()));
+    println!("IP:     {} / {}", or_dash(ip_addr), or_dash(subnet));
+    println!("MAC:    {}", or_dash(mac));
+    println!("Pages:  {}", or_dash(pages));
+    println!("Uptime: {}", or_dash(uptime));
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn convert(options: &Options) -> CliResult<ExitCode> {
+    let media = options.media_or_err()?;
+    let job = options.job(media)?;
+    let output = options.output()?;
+
+    let mut printer = printer::to_file(output)?;
+    printer.set_model(options.model_for(media));
+    printer.print_job(&job)?;
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn preview(options: &Options) -> CliResult<ExitCode> {
+    let media = options.media_or_err()?;
+    let job = options.job(media)?;
+    let output = options.output()?;
+
+    let model = options.model_for(media);
+    let mut printer = PTouchPrinter::from_interface(Emulator::new(model, Some(media)));
+    printer.set_model(model);
+    printer.preflight(&job)?;
+    printer.print_job(&job)?;
+
+    let pages = printer.interface.take_pages();
+    if let [page] = pages.as_slice() {
+        page.save_png(output)?;
+    } else {
+        // One file per page, e.g. label-1.png, label-2.png
+        let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+        for (n, page) in pages.iter().enumerate() {
+            page.save_png(output.with_file_name(format!("{stem}-{}.png", n + 1)))?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn discover(options: &Options) -> CliResult<ExitCode> {
+    let mut discovery = Discovery::new()
+        .mdns(options.mdns)
+        .timeout(options.timeout.unwrap_or(Duration::from_secs(2)));
+    if let Some((addr, prefix_len)) = options.subnet {
+        discovery = discovery.subnet(addr, prefix_len);
+    }
+
+    for found in discovery.run()? {
+        let media = found.status.as_ref().and_then(Media::from_status);
+        println!(
+            "{:<15} {:<20} {:<18} {:<12} {}",
+            found.ip,
+            found.model.as_deref().unwrap_or("-"),
+            found.name.as_deref().unwrap_or("-"),
+            found.serial.as_deref().unwrap_or("-"),
+            media.map_or("-".to_string(), |media| media.to_string())
+        );
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn list_media() -> CliResult<ExitCode> {
+    for media in media::all() {
+        if let Some(product) = media.product {
+            println!("{product:<10} {media}");
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+#[test]
+fn parse_options() {
+    let args = "print -m dk-22205 a.png b.png --copies 3 --dither 100 --cut-every 2 -p printer";
+    let options = Options::parse(args.split(' ').map(String::from).collect()).unwrap();
+
+    assert_eq!(options.command, "print");
+    assert_eq!(
+        options.images,
+        [PathBuf::from("a.png"), PathBuf::from("b.png")]
+    );
+    assert_eq!(options.media.unwrap().product, Some("DK-22205"));
+    assert_eq!(options.copies, 3);
+    assert_eq!(options.dither, Dither::Threshold(100));
+    assert_eq!(options.cut, CutMode::Every(2));
+    assert_eq!(
+        network_addr(options.printer_spec().unwrap()).unwrap(),
+        "printer:9100"
+    );
+    assert_eq!(network_addr("usb:/dev/usb/lp0"), None);
+
+    assert_eq!(
+        parse_subnet("10.0.0.0/8").unwrap(),
+        (Ipv4Addr::new(10, 0, 0, 0), 8)
+    );
+    assert!(parse_subnet("10.0.0.0/33").is_err());
+
+    assert_eq!(parse_model("QL-820NWB").unwrap(), Model::QL820NWB);
+    assert!(parse_model("QL-9000").is_err());
+    assert!(Options::parse(vec!["print".into(), "--copies".into()]).is_err());
+    assert!(Options::parse(vec!["print".into(), "-m".into(), "DK-0".into()]).is_err());
+}
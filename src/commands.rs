@@ -1,6 +1,15 @@
+use std::io;
+
 use bitflags::bitflags;
+use num_enum::TryFromPrimitive;
 
-use crate::{prelude::*, printer::PTouchPrinter, status::MediaKind};
+use crate::{
+    packbits,
+    prelude::*,
+    printer::PTouchPrinter,
+    status::{Capabilities, MediaKind},
+    PTouchError,
+};
 
 // Raw command API for the PTouch device.
 /// This provides low-level access to the device (if desired)
@@ -15,13 +24,13 @@ pub trait Commands {
     fn invalidate(&mut self) -> Result<()>;
 
     /// Issue a status request
-    // fn status_req(&mut self) -> Result<()>;
+    fn status_req(&mut self) -> Result<()>;
 
-    /// Read a status response with the provided timeout
-    // fn read_status(&mut self) -> Result<Status>;
+    /// Read a status response, bounded by the read timeout of the interface
+    fn read_status(&mut self) -> Result<Status>;
 
     /// Set status notify (printer automatically sends status on change)
-    // fn set_status_notify(&mut self, enabled: bool) -> Result<()>;
+    fn set_status_notify(&mut self, enabled: bool) -> Result<()>;
 
     /// Switch mode, required for raster printing
     fn switch_mode(&mut self, mode: Mode) -> Result<()>;
@@ -42,13 +51,19 @@ pub trait Commands {
     fn set_page_no(&mut self, no: u8) -> Result<()>;
 
     /// Set compression mode (None or Tiff).
-    /// Note TIFF mode is currently... broken
+    /// Subsequent raster lines are PackBits encoded when TIFF mode is set.
+    /// P-touch tape engines always use TIFF mode, models without compression support never do
     fn set_compression_mode(&mut self, mode: CompressionMode) -> Result<()>;
 
-    /// Transfer raster data
+    /// Transfer (uncompressed) raster data, encoded according to the compression mode and
+    /// framed for the print engine. In TIFF mode an all-zero line is sent as a zero raster line
     fn transfer_raster_line(&mut self, data: &[u8]) -> Result<()>;
 
-    /// Send a zero raster line
+    /// Transfer a two-colour raster line, the black (high energy) and red (low energy) planes.
+    /// Requires `AdvancedMode::TWO_COLOUR`, the pair counts as a single line for `PrintInfo::raster_no`
+    fn transfer_two_colour_raster_line(&mut self, black: &[u8], red: &[u8]) -> Result<()>;
+
+    /// Send a zero raster line (TIFF mode only)
     fn raster_zero(&mut self) -> Result<()>;
 
     /// Start a print
@@ -56,138 +71,152 @@ pub trait Commands {
 
     /// Start a print and feed
     fn print_and_feed(&mut self) -> Result<()>;
+
+    /// Capabilities of the printer when known, `PrintJob::send` leaves out what it can't do
+    fn capabilities(&self) -> Option<Capabilities> {
+        None
+    }
 }
 
 /// Low-level command API implementation
 impl<I: PTouchInterface> Commands for PTouchPrinter<I> {
     fn null(&mut self) -> Result<()> {
-        self.write([0x00])
+        self.send(&Command::Invalidate(1))
     }
 
     fn invalidate(&mut self) -> Result<()> {
-        self.write([0u8; 400])
+        self.send(&Command::Invalidate(400))
     }
 
     fn init(&mut self) -> Result<()> {
-        self.write([0x1b, 0x40])
+        self.send(&Command::Init)
     }
 
     fn switch_mode(&mut self, mode: Mode) -> Result<()> {
-        self.write([0x1b, 0x69, 0x61, mode as u8])
+        self.send(&Command::SwitchMode(mode))
     }
 
-    // fn set_status_notify(&mut self, enabled: bool) -> Result<()> {
-    //     let en = match enabled {
-    //         true => 0,
-    //         false => 1,
-    //     };
-
-    //     self.write(&[0x1b, 0x69, 0x21, en])
-    // }
-
-    fn set_print_info(&mut self, info: &PrintInfo) -> Result<()> {
-        let mut buff = [0u8; 13];
-
-        // debug!("Set print info: {:?}", info);
-
-        // Command header
-        buff[0] = 0x1b;
-        buff[1] = 0x69;
-        buff[2] = 0x7a;
+    fn status_req(&mut self) -> Result<()> {
+        self.send(&Command::StatusRequest)
+    }
 
-        if let Some(i) = &info.kind {
-            buff[3] |= 0x02;
-            buff[4] = u8::from(*i)
-        }
+    fn read_status(&mut self) -> Result<Status> {
+        loop {
+            let n = self.interface.read(self.protocol.status_buf())?;
+            if n == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
 
-        if let Some(w) = &info.width {
-            buff[3] |= 0x04;
-            buff[5] = *w;
-        }
-
-        if let Some(l) = &info.length {
-            buff[3] |= 0x08;
-            buff[6] = *l;
+            if let Some(status) = self.protocol.status_read(n) {
+                return status;
+            }
         }
+    }
 
-        let raster_bytes = info.raster_no.to_le_bytes();
-        buff[7..11].copy_from_slice(&raster_bytes);
+    fn set_status_notify(&mut self, enabled: bool) -> Result<()> {
+        self.send(&Command::StatusNotify(enabled))
+    }
 
-        if info.recover {
-            buff[3] |= 0x80;
-        }
+    fn set_print_info(&mut self, info: &PrintInfo) -> Result<()> {
+        // debug!("Set print info: {:?}", info);
 
-        self.write(&buff)
+        self.send(&Command::PrintInfo(info.clone()))
     }
 
     fn set_various_mode(&mut self, mode: VariousMode) -> Result<()> {
         // debug!("Set various mode: {:?}", mode);
 
-        self.write([0x1b, 0x69, 0x4d, mode.bits()])
+        self.send(&Command::VariousMode(mode))
     }
 
     fn set_advanced_mode(&mut self, mode: AdvancedMode) -> Result<()> {
         // debug!("Set advanced mode: {:?}", mode);
 
-        self.write([0x1b, 0x69, 0x4b, mode.bits()])
+        self.send(&Command::AdvancedMode(mode))
     }
 
     fn set_margin(&mut self, dots: u16) -> Result<()> {
         // debug!("Set margin: {:?}", dots);
 
-        self.write([0x1b, 0x69, 0x64, dots as u8, (dots >> 8) as u8])
+        self.send(&Command::Margin(dots))
     }
 
     fn set_page_no(&mut self, no: u8) -> Result<()> {
         // debug!("Set page no: {:?}", no);
-        self.write([0x1b, 0x69, 0x41, no])
+        self.send(&Command::PageNo(no))
     }
 
     fn set_compression_mode(&mut self, mode: CompressionMode) -> Result<()> {
         // debug!("Set compression mode: {:?}", mode);
 
-        self.write([0x4D, mode as u8])
+        let command = self.protocol.compression_mode(mode);
+        self.send(&command)
     }
 
-    // We assume an uncomressed line!
     fn transfer_raster_line(&mut self, data: &[u8]) -> Result<()> {
-        let mut buff = Vec::with_capacity(data.len() + 3);
-        buff.push(0x67); // Transfer raster data command
-
-        // alternative 'engine' (used by handheld labelers)
-        // buff.push((data.len() & 0xFF) as u8);
-        // buff.push((data.len() >> 8) as u8);
+        // trace!("Raster transfer: {:02x?}", data);
 
-        buff.push(0); // 'always 0'
-        buff.push(data.len() as u8); // add data
-
-        buff.extend_from_slice(data);
+        let command = self.protocol.raster_line(data);
+        self.send(&command)
+    }
 
-        // trace!("Raster transfer: {:02x?}", &buff[..3 + data.len()]);
-        // println!("Raster transfer: {:02x?}", &buff);
+    fn transfer_two_colour_raster_line(&mut self, black: &[u8], red: &[u8]) -> Result<()> {
+        for command in self.protocol.two_colour_raster_line(black, red) {
+            self.send(&command)?;
+        }
 
-        self.write(buff.as_slice())
+        Ok(())
     }
 
     fn raster_zero(&mut self) -> Result<()> {
         // debug!("Raster zero line");
 
-        self.write([0x5a])
+        self.send(&Command::RasterZero)
     }
 
     fn print(&mut self) -> Result<()> {
         // debug!("Print command");
-        self.write([0x0c])
+        self.send(&Command::Print)
     }
 
     fn print_and_feed(&mut self) -> Result<()> {
         // debug!("Print feed command");
-        self.write([0x1a])
+        self.send(&Command::PrintAndFeed)
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.protocol.capabilities()
+    }
+}
+
+/// Parse a status frame read from the data channel
+pub(crate) fn status_frame(frame: [u8; 32]) -> Result<Status> {
+    // Print head mark and status size are fixed
+    if frame[0] != 0x80 || frame[1] != 0x20 {
+        return Err(PTouchError::InvalidStatusPayload);
+    }
+
+    Ok(Status::from(frame))
+}
+
+/// Compression mode the printer accepts in place of `mode`
+pub(crate) fn supported_compression(
+    mode: CompressionMode,
+    engine: Engine,
+    capabilities: Option<&Capabilities>,
+) -> CompressionMode {
+    match engine.is_tape() {
+        // P-touch tape engines require TIFF
+        true => CompressionMode::Tiff,
+        // e.g. the QL-800 does not support compression
+        false if capabilities.is_some_and(|c| !c.compression) => CompressionMode::None,
+        false => mode,
     }
 }
 
 /// Device mode for set_mode command
-#[derive(Copy, Clone, PartialEq, Debug, Default)]
+#[derive(Copy, Clone, PartialEq, Debug, Default, TryFromPrimitive)]
+#[repr(u8)]
 pub enum Mode {
     /// ESC/P mode (legacy hi-level Epson mode)
     EscP = 0x00,
@@ -211,6 +240,8 @@ bitflags! {
     /// Advanced mode flags
     #[derive(Copy, Clone, PartialEq, Debug)]
     pub struct AdvancedMode: u8 {
+        /// Black/red printing on two-colour media (QL-8xx)
+        const TWO_COLOUR = (1 << 0);
         const HALF_CUT = (1 << 2);
         const NO_CHAIN = (1 << 3);
         const SPECIAL_TAPE = (1 << 4);
@@ -230,10 +261,68 @@ pub struct PrintInfo {
     pub length: Option<u8>,
     /// Raster number (??)
     pub raster_no: u32,
+    /// Give priority to print quality (not available for two-colour printing)
+    pub quality: bool,
+    /// First page of the job (multi page jobs)
+    pub first_page: bool,
     /// Enable print recovery
     pub recover: bool,
 }
 
+impl PrintInfo {
+    /// The 10 argument bytes of the print information command
+    pub fn to_bytes(&self) -> [u8; 10] {
+        let mut n = [0u8; 10];
+
+        if let Some(kind) = self.kind {
+            n[0] |= 0x02;
+            n[1] = u8::from(kind);
+        }
+
+        if let Some(width) = self.width {
+            n[0] |= 0x04;
+            n[2] = width;
+        }
+
+        if let Some(length) = self.length {
+            n[0] |= 0x08;
+            n[3] = length;
+        }
+
+        n[4..8].copy_from_slice(&self.raster_no.to_le_bytes());
+
+        if self.quality {
+            n[0] |= 0x40;
+        }
+
+        if self.recover {
+            n[0] |= 0x80;
+        }
+
+        n[8] = match self.first_page {
+            true => 0,
+            false => 1,
+        };
+
+        n
+    }
+
+    /// Parse the 10 argument bytes of the print information command
+    pub fn from_bytes(n: [u8; 10]) -> Self {
+        let flags = n[0];
+
+        Self {
+            kind: (flags & 0x02 != 0).then(|| MediaKind::from(n[1])),
+            width: (flags & 0x04 != 0).then_some(n[2]),
+            length: (flags & 0x08 != 0).then_some(n[3]),
+            raster_no: u32::from_le_bytes([n[4], n[5], n[6], n[7]]),
+            quality: flags & 0x40 != 0,
+            first_page: n[8] == 0,
+            recover: flags & 0x80 != 0,
+        }
+    }
+}
+
 impl Default for PrintInfo {
     fn default() -> Self {
         Self {
@@ -241,14 +330,354 @@ impl Default for PrintInfo {
             width: None,
             length: Some(0),
             raster_no: 0,
+            quality: false,
+            first_page: true,
             recover: true,
         }
     }
 }
 
 /// Compression mode enumeration
-#[derive(Copy, Clone, PartialEq, Debug)]
+#[derive(Copy, Clone, PartialEq, Debug, Default, TryFromPrimitive)]
+#[repr(u8)]
 pub enum CompressionMode {
+    #[default]
     None = 0x00,
     Tiff = 0x02,
 }
+
+/// A single command of the raster command stream, `Commands` sends these encoded
+#[derive(Clone, PartialEq, Debug)]
+pub enum Command {
+    /// Run of null bytes, 400 of them invalidate any pending command
+    Invalidate(usize),
+    Init,
+    StatusRequest,
+    StatusNotify(bool),
+    SwitchMode(Mode),
+    PrintInfo(PrintInfo),
+    VariousMode(VariousMode),
+    AdvancedMode(AdvancedMode),
+    Margin(u16),
+    PageNo(u8),
+    CompressionMode(CompressionMode),
+    /// Raster line as transferred, PackBits encoded in TIFF mode
+    RasterLine(Vec<u8>),
+    /// P-touch framed raster line, always PackBits encoded
+    TapeRasterLine(Vec<u8>),
+    /// Black plane of a two-colour raster line, as transferred
+    BlackRasterLine(Vec<u8>),
+    /// Red plane of a two-colour raster line, as transferred
+    RedRasterLine(Vec<u8>),
+    RasterZero,
+    Print,
+    PrintAndFeed,
+    /// Additional media information (`ESC i U w 01`) sent by the Brother drivers, 127 bytes
+    MediaInfo(Vec<u8>),
+    /// Job ID (`ESC i U J`) sent by the Brother drivers, 14 bytes
+    JobId(Vec<u8>),
+    /// Number of copies (`ESC i k c`) sent by the Brother drivers
+    Copies(u16),
+    /// Bytes not recognised as a command
+    Unknown(Vec<u8>),
+}
+
+impl Command {
+    /// Raster line command for the print engine, `data` is encoded according to `compression`.
+    /// In TIFF mode an all-zero line becomes a zero raster line
+    pub fn raster_line(data: &[u8], engine: Engine, compression: CompressionMode) -> Self {
+        let data = match compression {
+            CompressionMode::None => data.to_vec(),
+            CompressionMode::Tiff if data.iter().all(|&b| b == 0) => return Self::RasterZero,
+            CompressionMode::Tiff => packbits::encode(data),
+        };
+
+        match engine.is_tape() {
+            true => Self::TapeRasterLine(data),
+            false => Self::RasterLine(data),
+        }
+    }
+
+    /// Black and red plane commands of a two-colour raster line
+    pub fn two_colour_raster_line(
+        black: &[u8],
+        red: &[u8],
+        compression: CompressionMode,
+    ) -> [Self; 2] {
+        let encode = |data: &[u8]| match compression {
+            CompressionMode::None => data.to_vec(),
+            CompressionMode::Tiff => packbits::encode(data),
+        };
+
+        [
+            Self::BlackRasterLine(encode(black)),
+            Self::RedRasterLine(encode(red)),
+        ]
+    }
+
+    /// Encode the command into its byte representation
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buff = Vec::new();
+        self.encode_into(&mut buff);
+        buff
+    }
+
+    /// Append the byte representation of the command to `buff`
+    pub fn encode_into(&self, buff: &mut Vec<u8>) {
+        match self {
+            Self::Invalidate(n) => buff.resize(buff.len() + n, 0x00),
+            Self::Init => buff.extend([0x1b, 0x40]),
+            Self::StatusRequest => buff.extend([0x1b, 0x69, 0x53]),
+            Self::StatusNotify(enabled) => buff.extend([0x1b, 0x69, 0x21, !enabled as u8]),
+            Self::SwitchMode(mode) => buff.extend([0x1b, 0x69, 0x61, *mode as u8]),
+            Self::PrintInfo(info) => {
+                buff.extend([0x1b, 0x69, 0x7a]);
+                buff.extend(info.to_bytes());
+            }
+            Self::VariousMode(mode) => buff.extend([0x1b, 0x69, 0x4d, mode.bits()]),
+            Self::AdvancedMode(mode) => buff.extend([0x1b, 0x69, 0x4b, mode.bits()]),
+            Self::Margin(dots) => {
+                buff.extend([0x1b, 0x69, 0x64]);
+                buff.extend(dots.to_le_bytes());
+            }
+            Self::PageNo(no) => buff.extend([0x1b, 0x69, 0x41, *no]),
+            Self::CompressionMode(mode) => buff.extend([0x4d, *mode as u8]),
+            Self::RasterLine(data) => {
+                // Second byte is 'always 0'
+                buff.extend([0x67, 0x00, data.len() as u8]);
+                buff.extend_from_slice(data);
+            }
+            Self::TapeRasterLine(data) => {
+                // P-touch engine (used by handheld labelers), two byte length
+                buff.push(0x47);
+                buff.extend((data.len() as u16).to_le_bytes());
+                buff.extend_from_slice(data);
+            }
+            Self::BlackRasterLine(data) | Self::RedRasterLine(data) => {
+                let colour = match self {
+                    Self::RedRasterLine(_) => 0x02,
+                    _ => 0x01,
+                };
+                buff.extend([0x77, colour, data.len() as u8]);
+                buff.extend_from_slice(data);
+            }
+            Self::RasterZero => buff.push(0x5a),
+            Self::Print => buff.push(0x0c),
+            Self::PrintAndFeed => buff.push(0x1a),
+            Self::MediaInfo(data) => {
+                buff.extend([0x1b, 0x69, 0x55, 0x77, 0x01]);
+                buff.extend_from_slice(data);
+            }
+            Self::JobId(data) => {
+                buff.extend([0x1b, 0x69, 0x55, 0x4a]);
+                buff.extend_from_slice(data);
+            }
+            Self::Copies(n) => {
+                buff.extend([0x1b, 0x69, 0x6b, 0x63]);
+                buff.extend(n.to_le_bytes());
+            }
+            Self::Unknown(data) => buff.extend_from_slice(data),
+        }
+    }
+
+    /// Encode a sequence of commands, e.g. to store a job as a `.prn` file
+    pub fn encode_all<'a>(commands: impl IntoIterator<Item = &'a Command>) -> Vec<u8> {
+        let mut buff = Vec::new();
+        for command in commands {
+            command.encode_into(&mut buff);
+        }
+        buff
+    }
+
+    /// Decode the command at the start of `data`, returns the command and its length in bytes
+    /// or `None` when `data` ends before the command does
+    pub fn decode(data: &[u8]) -> Option<(Self, usize)> {
+        let arg = |n: usize| data.get(n).copied();
+        let unknown = |len: usize| Some((Self::Unknown(data[..len].to_vec()), len));
+        let line = |start: usize, len: usize| data.get(start..start + len).map(<[u8]>::to_vec);
+
+        let command = match *data.first()? {
+            0x00 => {
+                let len = data.iter().take_while(|&&b| b == 0).count();
+                return Some((Self::Invalidate(len), len));
+            }
+            0x1b => match (arg(1)?, arg(2)) {
+                (0x40, _) => return Some((Self::Init, 2)),
+                (0x69, None) => return None,
+                (0x69, Some(0x53)) => return Some((Self::StatusRequest, 3)),
+                (0x69, Some(0x7a)) => {
+                    let n: [u8; 10] = data.get(3..13)?.try_into().ok()?;
+                    return Some((Self::PrintInfo(PrintInfo::from_bytes(n)), 13));
+                }
+                (0x69, Some(0x64)) => {
+                    let dots = u16::from_le_bytes([arg(3)?, arg(4)?]);
+                    return Some((Self::Margin(dots), 5));
+                }
+                (0x69, Some(0x21)) => Self::StatusNotify(arg(3)? == 0),
+                (0x69, Some(0x61)) => match Mode::try_from(arg(3)?) {
+                    Ok(mode) => Self::SwitchMode(mode),
+                    Err(_) => return unknown(4),
+                },
+                (0x69, Some(0x4d)) => Self::VariousMode(VariousMode::from_bits_retain(arg(3)?)),
+                (0x69, Some(0x4b)) => Self::AdvancedMode(AdvancedMode::from_bits_retain(arg(3)?)),
+                (0x69, Some(0x41)) => Self::PageNo(arg(3)?),
+                (0x69, Some(0x55)) => {
+                    return match arg(3)? {
+                        0x77 if arg(4)? == 0x01 => Some((Self::MediaInfo(line(5, 127)?), 132)),
+                        0x4a => Some((Self::JobId(line(4, 14)?), 18)),
+                        _ => unknown(4),
+                    }
+                }
+                (0x69, Some(0x6b)) => {
+                    return match arg(3)? {
+                        0x63 => {
+                            let n = u16::from_le_bytes([arg(4)?, arg(5)?]);
+                            Some((Self::Copies(n), 6))
+                        }
+                        _ => unknown(4),
+                    }
+                }
+                // E.g. `ESC i X G`, the driver's config request
+                (0x69, Some(0x58)) => return unknown(4),
+                // Skip at least the command prefix, so its arguments are not taken as commands
+                (0x69, Some(_)) => return unknown(3),
+                _ => return unknown(2),
+            },
+            0x4d => {
+                return match CompressionMode::try_from(arg(1)?) {
+                    Ok(mode) => Some((Self::CompressionMode(mode), 2)),
+                    Err(_) => unknown(2),
+                }
+            }
+            0x67 => {
+                let len = arg(2)? as usize;
+                return Some((Self::RasterLine(line(3, len)?), 3 + len));
+            }
+            0x47 => {
+                let len = u16::from_le_bytes([arg(1)?, arg(2)?]) as usize;
+                return Some((Self::TapeRasterLine(line(3, len)?), 3 + len));
+            }
+            0x77 => {
+                let len = arg(2)? as usize;
+                let data = line(3, len)?;
+                return match arg(1)? {
+                    0x01 => Some((Self::BlackRasterLine(data), 3 + len)),
+                    0x02 => Some((Self::RedRasterLine(data), 3 + len)),
+                    _ => unknown(3 + len),
+                };
+            }
+            0x5a => return Some((Self::RasterZero, 1)),
+            0x0c => return Some((Self::Print, 1)),
+            0x1a => return Some((Self::PrintAndFeed, 1)),
+            _ => return unknown(1),
+        };
+
+        // The remaining ESC i commands take a single argument
+        Some((command, 4))
+    }
+}
+
+#[test]
+fn compressed_raster_transfer() {
+    use crate::interface::TestInterface;
+
+    let mut line = [0u8; 90];
+    line[..4].copy_from_slice(&[0xff, 0xff, 0xff, 0x01]);
+
+    let mut printer = PTouchPrinter::from_interface(TestInterface::default());
+    printer.transfer_raster_line(&line).unwrap();
+    assert_eq!(printer.interface.sent.len(), 3 + 90);
+
+    printer.interface.sent.clear();
+    printer.set_compression_mode(CompressionMode::Tiff).unwrap();
+    printer.transfer_raster_line(&line).unwrap();
+    printer.transfer_raster_line(&[0u8; 90]).unwrap();
+    assert_eq!(
+        printer.interface.sent,
+        [0x4d, 0x02, 0x67, 0x00, 0x06, 0xfe, 0xff, 0x00, 0x01, 0xab, 0x00, 0x5a]
+    );
+
+    // Init resets the device, and thereby the compression mode
+    printer.init().unwrap();
+    printer.interface.sent.clear();
+    printer.transfer_raster_line(&[0u8; 90]).unwrap();
+    assert_eq!(printer.interface.sent.len(), 3 + 90);
+
+    // The QL-800 does not support compression
+    printer.set_model(crate::status::Model::QL800);
+    printer.interface.sent.clear();
+    printer.set_compression_mode(CompressionMode::Tiff).unwrap();
+    printer.transfer_raster_line(&[0u8; 90]).unwrap();
+    assert_eq!(printer.interface.sent.len(), 2 + 3 + 90);
+    assert_eq!(printer.interface.sent[..2], [0x4d, 0x00]);
+}
+
+#[test]
+fn ptouch_raster_framing() {
+    use crate::{interface::TestInterface, status::Model};
+
+    let mut printer = PTouchPrinter::from_interface(TestInterface::default());
+    printer.set_model(Model::PTP700);
+    assert_eq!(printer.engine(), Engine::HandHeld);
+
+    let mut line = [0u8; 16];
+    line[8] = 0x0f;
+
+    // TIFF is mandatory on the P-touch engine
+    printer.set_compression_mode(CompressionMode::None).unwrap();
+    printer.transfer_raster_line(&line).unwrap();
+    printer.transfer_raster_line(&[0u8; 16]).unwrap();
+    assert_eq!(
+        printer.interface.sent,
+        [0x4d, 0x02, 0x47, 0x06, 0x00, 0xf9, 0x00, 0x00, 0x0f, 0xfa, 0x00, 0x5a]
+    );
+
+    // Unknown models keep the current engine
+    printer.set_model(Model::Unknown(0x99));
+    assert_eq!(printer.engine(), Engine::HandHeld);
+}
+
+#[test]
+fn command_roundtrip() {
+    let commands = [
+        Command::Invalidate(400),
+        Command::Init,
+        Command::StatusRequest,
+        Command::StatusNotify(false),
+        Command::SwitchMode(Mode::Raster),
+        Command::PrintInfo(PrintInfo {
+            kind: Some(MediaKind::DieCutLabels),
+            width: Some(29),
+            length: Some(90),
+            raster_no: 991,
+            quality: true,
+            first_page: false,
+            recover: true,
+        }),
+        Command::PrintInfo(PrintInfo::default()),
+        Command::VariousMode(VariousMode::AUTO_CUT),
+        Command::AdvancedMode(AdvancedMode::NO_CHAIN | AdvancedMode::HIGH_RES),
+        Command::Margin(0x0123),
+        Command::PageNo(2),
+        Command::CompressionMode(CompressionMode::Tiff),
+        Command::raster_line(&[0x0f; 90], Engine::DeskLabel, CompressionMode::Tiff),
+        Command::raster_line(&[0x0f; 16], Engine::HandHeld, CompressionMode::Tiff),
+        Command::raster_line(&[0x00; 90], Engine::DeskLabel, CompressionMode::Tiff),
+        Command::BlackRasterLine(vec![0xaa; 90]),
+        Command::RedRasterLine(vec![0x55; 90]),
+        Command::Print,
+        Command::PrintAndFeed,
+        Command::MediaInfo(vec![0x77; 127]),
+        Command::JobId(vec![0x67; 14]),
+        Command::Copies(300),
+        Command::Unknown(vec![0xff]),
+    ];
+
+    let encoded = Command::encode_all(&commands);
+    assert_eq!(crate::disasm::disassemble(&encoded).commands, commands);
+
+    assert_eq!(commands[12], Command::RasterLine(vec![0xa7, 0x0f]));
+    assert_eq!(commands[13].encode(), [0x47, 0x02, 0x00, 0xf1, 0x0f]);
+    assert_eq!(commands[14], Command::RasterZero);
+    assert_eq!(commands[9].encode(), [0x1b, 0x69, 0x64, 0x23, 0x01]);
+}
@@ -0,0 +1,239 @@
+//! Command stream disassembler.
+//!
+//! Turns a raw byte stream, e.g. a captured `.prn` file or the output of the Brother driver, back
+//! into a sequence of `Command`s. The `Display` implementation dumps one command per line, with
+//! raster lines decompressed, so the output of two drivers can be compared using `diff`.
+
+use std::fmt::{self, Display, Formatter, Write};
+
+use crate::{
+    commands::{Command, CompressionMode, PrintInfo},
+    packbits,
+};
+
+/// Decoded command stream
+#[derive(Clone, PartialEq, Debug)]
+pub struct Disassembly {
+    pub commands: Vec<Command>,
+    /// Bytes of an incomplete command at the end of the stream
+    pub trailing: Vec<u8>,
+}
+
+/// Decode a raw command stream
+pub fn disassemble(data: &[u8]) -> Disassembly {
+    let mut commands = Vec::new();
+    let mut pos = 0;
+
+    while let Some((command, len)) = Command::decode(&data[pos..]) {
+        commands.push(command);
+        pos += len;
+    }
+
+    Disassembly {
+        commands,
+        trailing: data[pos..].to_vec(),
+    }
+}
+
+impl Display for Disassembly {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let mut compression = CompressionMode::None;
+
+        for command in &self.commands {
+            match command {
+                Command::Invalidate(400) => writeln!(f, "invalidate")?,
+                Command::Invalidate(n) => writeln!(f, "null x{n}")?,
+                Command::Init => {
+                    compression = CompressionMode::None;
+                    writeln!(f, "init")?
+                }
+                Command::StatusRequest => writeln!(f, "status request")?,
+                Command::StatusNotify(notify) => {
+                    writeln!(f, "status notify {}", if *notify { "on" } else { "off" })?
+                }
+                Command::SwitchMode(mode) => writeln!(f, "switch mode {mode:?}")?,
+                Command::PrintInfo(info) => writeln!(f, "print info {}", print_info(info))?,
+                Command::VariousMode(mode) => writeln!(
+                    f,
+                    "various mode {:#04x} {}",
+                    mode.bits(),
+                    flags(mode.iter_names())
+                )?,
+                Command::AdvancedMode(mode) => writeln!(
+                    f,
+                    "advanced mode {:#04x} {}",
+                    mode.bits(),
+                    flags(mode.iter_names())
+                )?,
+                Command::Margin(dots) => writeln!(f, "margin {dots}")?,
+                Command::PageNo(n) => writeln!(f, "page no {n}")?,
+                Command::CompressionMode(mode) => {
+                    compression = *mode;
+                    writeln!(f, "compression {mode:?}")?
+                }
+                Command::RasterLine(data) => writeln!(f, "raster {}", raster(data, compression))?,
+                Command::TapeRasterLine(data) => {
+                    writeln!(f, "tape raster {}", raster(data, CompressionMode::Tiff))?
+                }
+                Command::BlackRasterLine(data) => {
+                    writeln!(f, "raster black {}", raster(data, compression))?
+                }
+                Command::RedRasterLine(data) => {
+                    writeln!(f, "raster red {}", raster(data, compression))?
+                }
+                Command::RasterZero => writeln!(f, "raster zero")?,
+                Command::Print => writeln!(f, "print")?,
+                Command::PrintAndFeed => writeln!(f, "print and feed")?,
+                Command::MediaInfo(data) => writeln!(f, "media info {}", hex(data))?,
+                Command::JobId(data) => writeln!(f, "job id {}", hex(data))?,
+                Command::Copies(n) => writeln!(f, "copies {n}")?,
+                Command::Unknown(data) => writeln!(f, "unknown {}", hex(data))?,
+            }
+        }
+
+        if !self.trailing.is_empty() {
+            writeln!(f, "incomplete {}", hex(&self.trailing))?;
+        }
+
+        Ok(())
+    }
+}
+
+fn print_info(info: &PrintInfo) -> String {
+    let opt = |v: Option<String>| v.unwrap_or_else(|| "-".to_string());
+
+    format!(
+        "kind={} width={} length={} raster_no={} quality={} first_page={} recover={}",
+        opt(info.kind.map(|k| format!("{k:?}"))),
+        opt(info.width.map(|w| w.to_string())),
+        opt(info.length.map(|l| l.to_string())),
+        info.raster_no,
+        info.quality,
+        info.first_page,
+        info.recover
+    )
+}
+
+fn flags<'a>(names: impl Iterator<Item = (&'a str, impl Sized)>) -> String {
+    names.map(|(name, _)| name).collect::<Vec<_>>().join("|")
+}
+
+/// Raster line data, uncompressed
+fn raster(data: &[u8], compression: CompressionMode) -> String {
+    match compression {
+        CompressionMode::None => hex(data),
+        CompressionMode::Tiff => match packbits::decode(data) {
+            Some(line) => hex(&line),
+            None => format!("(invalid packbits) {}", hex(data)),
+        },
+    }
+}
+
+fn hex(data: &[u8]) -> String {
+    data.iter().fold(String::new(), |mut s, b| {
+        let _ = write!(s, "{b:02x}");
+        s
+    })
+}
+
+#[test]
+fn disassemble_print_job() {
+    use crate::{
+        commands::{AdvancedMode, Mode},
+        interface::TestInterface,
+        job::PrintJob,
+        media,
+        prelude::*,
+    };
+
+    let media = media::by_product("DK-22205").unwrap();
+    let mut page = RasterBuffer::new(2);
+    page[1][89] = 0x0f;
+    let job = PrintJob::new()
+        .media(media)
+        .page(page)
+        .compression(CompressionMode::Tiff);
+
+    let mut printer = PTouchPrinter::from_interface(TestInterface::default());
+    printer.print_job(&job).unwrap();
+
+    let mut data = printer.interface.sent.clone();
+    data.extend([0x1b, 0x69, 0x7a, 0x00]);
+    let disassembly = disassemble(&data);
+
+    assert_eq!(disassembly.commands.len(), 12);
+    assert_eq!(disassembly.commands[2], Command::SwitchMode(Mode::Raster));
+    assert_eq!(
+        disassembly.commands[3],
+        Command::PrintInfo(PrintInfo {
+            raster_no: 2,
+            ..media.print_info()
+        })
+    );
+    assert_eq!(
+        disassembly.commands[6],
+        Command::AdvancedMode(AdvancedMode::NO_CHAIN)
+    );
+    assert_eq!(disassembly.trailing, [0x1b, 0x69, 0x7a, 0x00]);
+
+    let dump = disassembly.to_string();
+    let lines: Vec<&str> = dump.lines().collect();
+    assert_eq!(lines[..3], ["invalidate", "init", "switch mode Raster"]);
+    assert_eq!(
+        lines[3],
+        "print info kind=ContinuousLengthTape width=62 length=0 raster_no=2 quality=false \
+         first_page=true recover=true"
+    );
+    assert_eq!(lines[4], "various mode 0x40 AUTO_CUT");
+    assert_eq!(lines[8..10], ["compression Tiff", "raster zero"]);
+    assert_eq!(lines[10], format!("raster {}0f", "00".repeat(89)));
+    assert_eq!(lines[11..], ["print and feed", "incomplete 1b697a00"]);
+}
+
+#[test]
+fn disassemble_driver_header() {
+    use crate::commands::Mode;
+
+    // Job header as sent by the Brother driver, the driver commands contain bytes that would
+    // read as raster lines when skipped byte by byte
+    let mut media_info = vec![0x77, 0x01, 0x10, 0x67, 0x00, 0x20];
+    media_info.resize(127, 0x00);
+    let job_id = [0x67, 0x00, 0x05, 0x77, 0x02, 0x03, 0, 0, 0, 0, 0, 0, 0, 0];
+    let mut data = vec![0x00; 400];
+    data.extend([0x1b, 0x40, 0x1b, 0x69, 0x61, 0x01]);
+    data.extend([0x1b, 0x69, 0x55, 0x77, 0x01]);
+    data.extend(&media_info);
+    data.extend([0x1b, 0x69, 0x55, 0x4a]);
+    data.extend(job_id);
+    data.extend([0x1b, 0x69, 0x6b, 0x63, 0x02, 0x00]);
+    data.extend([0x1b, 0x69, 0x58, 0x47]);
+    data.extend([0x1b, 0x69, 0x79]);
+
+    let disassembly = disassemble(&data);
+    assert_eq!(
+        disassembly.commands[..6],
+        [
+            Command::Invalidate(400),
+            Command::Init,
+            Command::SwitchMode(Mode::Raster),
+            Command::MediaInfo(media_info),
+            Command::JobId(job_id.to_vec()),
+            Command::Copies(2),
+        ]
+    );
+    assert!(disassembly.commands.iter().all(|c| !matches!(
+        c,
+        Command::RasterLine(_)
+            | Command::TapeRasterLine(_)
+            | Command::BlackRasterLine(_)
+            | Command::RedRasterLine(_)
+    )));
+    assert_eq!(
+        disassembly.commands[6..],
+        [
+            Command::Unknown(vec![0x1b, 0x69, 0x58, 0x47]),
+            Command::Unknown(vec![0x1b, 0x69, 0x79]),
+        ]
+    );
+    assert_eq!(disassembly.trailing, []);
+}
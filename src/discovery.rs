@@ -0,0 +1,337 @@
+//! Network printer discovery.
+//!
+//! Broadcasts SNMP GET requests for the name, model, serial number and status of a printer and
+//! collects the answers of every printer on the subnet. Optionally the printers advertising the
+//! `_pdl-datastream._tcp` (raw port 9100) mDNS service are asked as well, for networks where
+//! broadcasts are filtered.
+//!
+//! ```no_run
+//! use ql_raster::discovery::Discovery;
+//!
+//! # fn main() -> ql_raster::Result<()> {
+//! for found in Discovery::new().mdns(true).run()? {
+//!     println!("{} {}", found.ip, found.model.as_deref().unwrap_or("?"));
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    collections::BTreeMap,
+    io,
+    net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket},
+    thread,
+    time::{Duration, Instant},
+};
+
+use snmp::{pdu, SnmpMessageType, SnmpPdu, Value};
+
+use crate::{interface::SnmpSettings, monitor::SnmpMonitor, prelude::*, printer::snmp_oid};
+
+/// Raw print port of network printers
+const RAW_PORT: u16 = 9100;
+const MDNS_ADDR: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::new(224, 0, 0, 251)), 5353);
+const MDNS_SERVICE: &[&str] = &["_pdl-datastream", "_tcp", "local"];
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// OIDs requested from every printer, the request id is `REQUEST_ID + index`
+const OIDS: [&[u32]; 4] = [
+    snmp_oid::NAME,
+    snmp_oid::MODEL,
+    snmp_oid::SERIAL,
+    snmp_oid::STATUS,
+];
+const REQUEST_ID: i32 = 0x5154;
+
+/// A printer that answered the discovery
+#[derive(Clone, PartialEq, Debug)]
+pub struct DiscoveredPrinter {
+    pub ip: IpAddr,
+    /// Node name, e.g. "BRW0080927AFBCE"
+    pub name: Option<String>,
+    /// Model description, e.g. "Brother QL-820NWB"
+    pub model: Option<String>,
+    pub serial: Option<String>,
+    pub status: Option<Status>,
+    /// Settings the printer was found with
+    snmp: SnmpSettings,
+}
+
+impl DiscoveredPrinter {
+    /// Address of the raw print port
+    pub fn addr(&self) -> SocketAddr {
+        SocketAddr::new(self.ip, RAW_PORT)
+    }
+
+    /// Connect to the printer, using the SNMP settings of the discovery
+    pub fn connect(&self) -> Result<PTouchPrinter<PTouchTcpInterface>> {
+        let mut printer = printer::from_addr(self.addr())?;
+        printer.interface.set_snmp_settings(self.snmp.clone());
+        Ok(printer)
+    }
+
+    /// SNMP client for the printer, to poll it without connecting to the print port
+    pub fn monitor(&self) -> SnmpMonitor {
+        SnmpMonitor::with_settings(self.ip, self.snmp.clone())
+    }
+
+    pub fn snmp_settings(&self) -> &SnmpSettings {
+        &self.snmp
+    }
+
+    fn new(ip: IpAddr, snmp: &SnmpSettings) -> Self {
+        Self {
+            ip,
+            name: None,
+            model: None,
+            serial: None,
+            status: None,
+            snmp: snmp.clone(),
+        }
+    }
+}
+
+/// Discovery settings, `run` to search
+#[derive(Clone, Debug)]
+pub struct Discovery {
+    targets: Vec<IpAddr>,
+    snmp: SnmpSettings,
+    timeout: Duration,
+    mdns: bool,
+}
+
+impl Default for Discovery {
+    fn default() -> Self {
+        Self {
+            targets: Vec::new(),
+            snmp: SnmpSettings::default(),
+            timeout: Duration::from_secs(2),
+            mdns: false,
+        }
+    }
+}
+
+impl Discovery {
+    /// Broadcast to 255.255.255.255, unless other targets are added
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send the requests to `addr`, a broadcast address or a single printer
+    pub fn target(mut self, addr: IpAddr) -> Self {
+        self.targets.push(addr);
+        self
+    }
+
+    /// Broadcast on the subnet of `addr`, e.g. `192.168.1.0/24` broadcasts to `192.168.1.255`
+    pub fn subnet(self, addr: Ipv4Addr, prefix_len: u8) -> Self {
+        let host_mask = u32::MAX.checked_shr(prefix_len.into()).unwrap_or(0);
+        self.target(Ipv4Addr::from(u32::from(addr) | host_mask).into())
+    }
+
+    /// SNMP settings of the printers, passed on to the found printers. Only the port and
+    /// community are used by the discovery itself
+    pub fn snmp_settings(mut self, settings: SnmpSettings) -> Self {
+        self.snmp = settings;
+        self
+    }
+
+    /// SNMP port of the printers, 161 by default
+    pub fn port(mut self, port: u16) -> Self {
+        self.snmp.port = port;
+        self
+    }
+
+    /// SNMP community, "public" by default
+    pub fn community(mut self, community: &str) -> Self {
+        self.snmp.community = community.into();
+        self
+    }
+
+    /// Time to wait for answers, 2 seconds by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Also ask the printers advertising the raw print service over mDNS
+    pub fn mdns(mut self, mdns: bool) -> Self {
+        self.mdns = mdns;
+        self
+    }
+
+    /// Search for printers, waits for the full timeout. Found printers are sorted by IP
+    pub fn run(&self) -> Result<Vec<DiscoveredPrinter>> {
+        let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+
+        let targets = match self.targets.as_slice() {
+            [] => &[IpAddr::V4(Ipv4Addr::BROADCAST)][..],
+            targets => targets,
+        };
+        for &target in targets {
+            self.send_requests(&socket, target)?;
+        }
+
+        let mdns_socket = match self.mdns {
+            true => Some(mdns_query()?),
+            false => None,
+        };
+
+        let mut found = BTreeMap::new();
+        let deadline = Instant::now() + self.timeout;
+        let mut buf = [0u8; 1500];
+
+        while Instant::now() < deadline {
+            let mut idle = true;
+
+            match socket.recv_from(&mut buf) {
+                Ok((n, peer)) => {
+                    idle = false;
+                    read_response(&buf[..n], peer.ip(), &self.snmp, &mut found);
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            if let Some(mdns_socket) = &mdns_socket {
+                match mdns_socket.recv_from(&mut buf) {
+                    Ok((n, peer)) => {
+                        idle = false;
+                        // Ask printers found over mDNS directly, unless they answered already
+                        if is_mdns_answer(&buf[..n]) && !found.contains_key(&peer.ip()) {
+                            let printer = DiscoveredPrinter::new(peer.ip(), &self.snmp);
+                            found.insert(peer.ip(), printer);
+                            self.send_requests(&socket, peer.ip())?;
+                        }
+                    }
+                    Err(e) if e.kind() == io::ErrorKind::WouldBlock => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+
+            if idle {
+                thread::sleep(POLL_INTERVAL);
+            }
+        }
+
+        Ok(found.into_values().collect())
+    }
+
+    fn send_requests(&self, socket: &UdpSocket, target: IpAddr) -> Result<()> {
+        let mut buf = pdu::Buf::default();
+
+        for (n, oid) in OIDS.iter().enumerate() {
+            pdu::build_get(
+                self.snmp.community.as_bytes(),
+                REQUEST_ID + n as i32,
+                oid,
+                &mut buf,
+            );
+            socket.send_to(&buf, (target, self.snmp.port))?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Discover printers with the default settings, see `Discovery`
+pub fn discover() -> Result<Vec<DiscoveredPrinter>> {
+    Discovery::new().run()
+}
+
+/// Add the value of an SNMP response to the printer it came from
+fn read_response(
+    data: &[u8],
+    ip: IpAddr,
+    snmp: &SnmpSettings,
+    found: &mut BTreeMap<IpAddr, DiscoveredPrinter>,
+) {
+    let Ok(mut response) = SnmpPdu::from_bytes(data) else {
+        return;
+    };
+    let index = response.req_id.wrapping_sub(REQUEST_ID);
+    if response.message_type != SnmpMessageType::Response || !(0..4).contains(&index) {
+        return;
+    }
+    let Some((_oid, Value::OctetString(value))) = response.varbinds.next() else {
+        return;
+    };
+
+    let printer = found
+        .entry(ip)
+        .or_insert_with(|| DiscoveredPrinter::new(ip, snmp));
+    let string = || Some(String::from_utf8_lossy(value).trim().to_string());
+    match index {
+        0 => printer.name = string(),
+        1 => printer.model = string(),
+        2 => printer.serial = string(),
+        _ => printer.status = Status::try_from(value).ok(),
+    }
+}
+
+/// Send an mDNS PTR query for the raw print service, asking for unicast answers
+fn mdns_query() -> Result<UdpSocket> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))?;
+    socket.set_nonblocking(true)?;
+
+    // Header: id, flags, 1 question, no answer, authority or additional records
+    let mut query = vec![0x51, 0x54, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in MDNS_SERVICE {
+        query.push(label.len() as u8);
+        query.extend(label.as_bytes());
+    }
+    // Root label, type PTR, class IN with the unicast response bit
+    query.extend([0, 0, 12, 0x80, 1]);
+
+    socket.send_to(&query, MDNS_ADDR)?;
+    Ok(socket)
+}
+
+/// A response (with answers) to our query
+fn is_mdns_answer(data: &[u8]) -> bool {
+    data.len() >= 12 && data[..2] == [0x51, 0x54] && data[2] & 0x80 != 0 && data[6..8] != [0, 0]
+}
+
+#[test]
+fn discover_emulated_printers() {
+    use crate::{
+        emulator::{Emulator, EmulatorServer},
+        media,
+        status::Model,
+    };
+
+    let media = media::by_product("DK-22205");
+    let server =
+        EmulatorServer::bind("127.0.0.1:0", Emulator::new(Model::QL820NWB, media)).unwrap();
+    let agent = server.serve_snmp("127.0.0.1:0").unwrap();
+
+    let found = Discovery::new()
+        .target(Ipv4Addr::LOCALHOST.into())
+        .port(agent.local_addr().port())
+        .timeout(Duration::from_millis(300))
+        .run()
+        .unwrap();
+
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].ip, IpAddr::from(Ipv4Addr::LOCALHOST));
+    assert_eq!(found[0].model.as_deref(), Some("Brother QL-820NWB"));
+    assert!(found[0].name.as_deref().unwrap().starts_with("BRN"));
+    assert!(found[0].serial.is_some());
+
+    let status = found[0].status.as_ref().unwrap();
+    assert_eq!(status.model, Model::QL820NWB);
+    assert_eq!(Media::from_status(status), media);
+
+    // Monitored with the settings it was found with
+    assert_eq!(found[0].snmp_settings().port, agent.local_addr().port());
+    assert_eq!(found[0].monitor().get_model().unwrap(), "Brother QL-820NWB");
+
+    assert_eq!(
+        Discovery::new()
+            .subnet(Ipv4Addr::new(192, 168, 1, 17), 24)
+            .targets,
+        [IpAddr::from(Ipv4Addr::new(192, 168, 1, 255))]
+    );
+}
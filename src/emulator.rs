@@ -0,0 +1,756 @@
+//! Virtual printer for offline testing.
+//!
+//! The `Emulator` consumes the raster command stream (it implements `PTouchInterface`, or can be
+//! served on a TCP port using `EmulatorServer`), answers status requests with 32 byte status
+//! frames and keeps every printed page, which can be rendered to an image. `SnmpAgent` answers
+//! the SNMP requests of a network printer for an emulator, e.g. for discovery.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+    net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs},
+    path::Path,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, MutexGuard,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+
+#[cfg(feature = "net")]
+use std::net::UdpSocket;
+
+use image::{Rgb, RgbImage};
+
+use crate::{
+    commands::{AdvancedMode, Command, CompressionMode, PrintInfo, VariousMode},
+    interface::PTouchInterface,
+    media::Media,
+    packbits,
+    raster::Engine,
+    status::{DeviceStatus, ErrorStatus, MediaKind, Model, TextColour},
+    Result,
+};
+
+/// A page as printed by the emulator
+#[derive(Clone, PartialEq, Debug)]
+pub struct PrintedPage {
+    pub engine: Engine,
+    /// Raster lines, uncompressed
+    pub lines: Vec<Vec<u8>>,
+    /// Red plane raster lines, empty for single colour pages
+    pub red: Vec<Vec<u8>>,
+    /// Raster number as announced in the print information
+    pub raster_no: u32,
+    pub auto_cut: bool,
+    pub high_res: bool,
+    /// Page ended with print and feed, i.e. last page of the job
+    pub feed: bool,
+}
+
+impl PrintedPage {
+    /// Render the page, dot `x` of `RasterBuffer::set_dot` becomes pixel column `x`
+    pub fn to_image(&self) -> RgbImage {
+        let mut image = RgbImage::from_pixel(
+            self.engine.dots(),
+            self.lines.len() as u32,
+            Rgb([255, 255, 255]),
+        );
+
+        let planes = [(&self.lines, Rgb([0, 0, 0])), (&self.red, Rgb([220, 0, 0]))];
+        for (plane, colour) in planes {
+            for (y, line) in plane.iter().enumerate() {
+                for x in 0..self.engine.dots() {
+                    let byte = line[line.len() - 1 - (x / 8) as usize];
+                    if byte & (1 << (x % 8)) != 0 && y < image.height() as usize {
+                        image.put_pixel(x, y as u32, colour);
+                    }
+                }
+            }
+        }
+
+        image
+    }
+
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.to_image()
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| match e {
+                image::ImageError::IoError(e) => e,
+                e => io::Error::other(e),
+            })?;
+
+        Ok(())
+    }
+}
+
+/// Emulated printer
+pub struct Emulator {
+    model: Model,
+    media: Option<Media>,
+    error: ErrorStatus,
+    notify: bool,
+
+    input: Vec<u8>,
+    output: VecDeque<u8>,
+
+    engine: Engine,
+    compression: CompressionMode,
+    various_mode: VariousMode,
+    advanced_mode: AdvancedMode,
+    raster_no: u32,
+    lines: Vec<Vec<u8>>,
+    red: Vec<Vec<u8>>,
+    pages: Vec<PrintedPage>,
+    /// Pages printed since the emulator was created, including taken ones
+    page_count: u64,
+    /// Time it takes to print a page, as seen over SNMP
+    print_time: Duration,
+    /// When the pages still being printed are done
+    printing: VecDeque<Instant>,
+}
+
+impl Emulator {
+    /// Emulate `model` with `media` loaded (`None` for no media)
+    pub fn new(model: Model, media: Option<&Media>) -> Self {
+        Self {
+            model,
+            media: media.cloned(),
+            error: ErrorStatus::empty(),
+            notify: true,
+            input: Vec::new(),
+            output: VecDeque::new(),
+            engine: model.engine().unwrap_or_default(),
+            compression: CompressionMode::None,
+            various_mode: VariousMode::empty(),
+            advanced_mode: AdvancedMode::empty(),
+            raster_no: 0,
+            lines: Vec::new(),
+            red: Vec::new(),
+            pages: Vec::new(),
+            page_count: 0,
+            print_time: Duration::ZERO,
+            printing: VecDeque::new(),
+        }
+    }
+
+    /// Simulate an error condition, e.g. `ErrorStatus::COVER_OPEN`
+    pub fn set_error(&mut self, error: ErrorStatus) {
+        self.error = error;
+    }
+
+    pub fn clear_error(&mut self) {
+        self.error = ErrorStatus::empty();
+    }
+
+    /// Swap the loaded media
+    pub fn set_media(&mut self, media: Option<&Media>) {
+        self.media = media.cloned();
+    }
+
+    /// Time it takes to print a page. Over SNMP the printer reports the printing phase and
+    /// counts the page once it is done, the data channel notifications are sent right away
+    pub fn set_print_time(&mut self, print_time: Duration) {
+        self.print_time = print_time;
+    }
+
+    /// All pages printed so far
+    pub fn pages(&self) -> &[PrintedPage] {
+        &self.pages
+    }
+
+    pub fn take_pages(&mut self) -> Vec<PrintedPage> {
+        std::mem::take(&mut self.pages)
+    }
+
+    /// Feed command stream data to the printer
+    pub fn feed(&mut self, data: &[u8]) {
+        self.input.extend_from_slice(data);
+
+        // An incomplete command at the end waits for more data
+        let mut pos = 0;
+        while let Some((command, len)) = Command::decode(&self.input[pos..]) {
+            pos += len;
+            self.execute(command);
+        }
+
+        self.input.drain(..pos);
+    }
+
+    fn execute(&mut self, command: Command) {
+        match command {
+            Command::Init => self.reset(),
+            Command::StatusRequest => self.send_status(DeviceStatus::Reply, 0),
+            Command::StatusNotify(notify) => self.notify = notify,
+            Command::PrintInfo(info) => self.print_info(&info),
+            Command::VariousMode(mode) => self.various_mode = mode,
+            Command::AdvancedMode(mode) => self.advanced_mode = mode,
+            Command::CompressionMode(mode) => self.compression = mode,
+            Command::RasterLine(data)
+            | Command::TapeRasterLine(data)
+            | Command::BlackRasterLine(data) => {
+                let line = self.decode_line(&data);
+                self.lines.push(line);
+            }
+            Command::RedRasterLine(data) => {
+                let line = self.decode_line(&data);
+                self.red.push(line);
+            }
+            Command::RasterZero => self.lines.push(vec![0; self.engine.bytes_per_line()]),
+            Command::Print => self.print(false),
+            Command::PrintAndFeed => self.print(true),
+            Command::Invalidate(_)
+            | Command::SwitchMode(_)
+            | Command::Margin(_)
+            | Command::PageNo(_)
+            | Command::MediaInfo(_)
+            | Command::JobId(_)
+            | Command::Copies(_)
+            | Command::Unknown(_) => {}
+        }
+    }
+
+    fn reset(&mut self) {
+        self.compression = CompressionMode::None;
+        self.various_mode = VariousMode::empty();
+        self.advanced_mode = AdvancedMode::empty();
+        self.lines.clear();
+        self.red.clear();
+    }
+
+    fn print_info(&mut self, info: &PrintInfo) {
+        self.raster_no = info.raster_no;
+
+        // Kind, width and length are only checked when given
+        let [kind, width, length] = self.media_status();
+        let mismatch = self.media.is_none()
+            && (info.kind.is_some() || info.width.is_some() || info.length.is_some())
+            || info.kind.is_some_and(|k| k != MediaKind::from(kind))
+            || info.width.is_some_and(|w| w != width)
+            || info.length.is_some_and(|l| l != length);
+
+        if mismatch {
+            self.error |= ErrorStatus::WRONG_MEDIA;
+        }
+    }
+
+    fn decode_line(&self, data: &[u8]) -> Vec<u8> {
+        let mut line = match self.compression {
+            CompressionMode::Tiff => packbits::decode(data).unwrap_or_default(),
+            CompressionMode::None => data.to_vec(),
+        };
+
+        line.resize(self.engine.bytes_per_line(), 0);
+        line
+    }
+
+    fn print(&mut self, feed: bool) {
+        let lines = std::mem::take(&mut self.lines);
+        let red = std::mem::take(&mut self.red);
+
+        if !self.error.is_empty() || self.media.is_none() {
+            if self.media.is_none() {
+                self.error |= ErrorStatus::NO_MEDIA;
+            }
+            self.send_notification(DeviceStatus::Error, 0);
+            return;
+        }
+
+        self.send_notification(DeviceStatus::PhaseChange, 1);
+        self.pages.push(PrintedPage {
+            engine: self.engine,
+            lines,
+            red,
+            raster_no: self.raster_no,
+            auto_cut: self.various_mode.contains(VariousMode::AUTO_CUT),
+            high_res: self.advanced_mode.contains(AdvancedMode::HIGH_RES),
+            feed,
+        });
+        self.page_count += 1;
+        if !self.print_time.is_zero() {
+            // Pages are printed one after the other
+            let now = Instant::now();
+            self.printing.retain(|&done| done > now);
+            let start = self.printing.back().map_or(now, |&done| done.max(now));
+            self.printing.push_back(start + self.print_time);
+        }
+        self.send_notification(DeviceStatus::Completed, 1);
+        self.send_notification(DeviceStatus::PhaseChange, 0);
+    }
+
+    fn send_notification(&mut self, status_type: DeviceStatus, phase: u8) {
+        // Errors are always reported, other status changes only in notification mode
+        if self.notify || status_type == DeviceStatus::Error {
+            self.send_status(status_type, phase);
+        }
+    }
+
+    /// Pages still being printed, see `set_print_time`
+    #[cfg(feature = "net")]
+    fn pages_printing(&self) -> u64 {
+        let now = Instant::now();
+        self.printing.iter().filter(|&&done| done > now).count() as u64
+    }
+
+    /// Media kind, width and length bytes as reported in the status
+    fn media_status(&self) -> [u8; 3] {
+        match &self.media {
+            Some(media) => {
+                let kind = media.print_info().kind.unwrap_or(MediaKind::None);
+                [u8::from(kind), media.width, media.length]
+            }
+            None => [0; 3],
+        }
+    }
+
+    fn send_status(&mut self, status_type: DeviceStatus, phase: u8) {
+        let frame = self.status_frame(status_type, phase);
+        self.output.extend(frame);
+    }
+
+    /// 32 byte status frame, as sent on the data channel (and returned by SNMP)
+    fn status_frame(&self, status_type: DeviceStatus, phase: u8) -> [u8; 32] {
+        let [kind, width, length] = self.media_status();
+        let [error_1, error_2] = self.error.bits().to_le_bytes();
+
+        let mut frame = [0u8; 32];
+        frame[..8].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, self.model.into(), 0x30, 0x30, 0]);
+        frame[8] = error_1;
+        frame[9] = error_2;
+        frame[10] = width;
+        frame[11] = kind;
+        frame[14] = 0x3f;
+        frame[15] = self.various_mode.bits();
+        frame[17] = length;
+        frame[18] = match status_type {
+            DeviceStatus::Reply => 0x00,
+            DeviceStatus::Completed => 0x01,
+            DeviceStatus::Error => 0x02,
+            DeviceStatus::ExitIF => 0x03,
+            DeviceStatus::TurnedOff => 0x04,
+            DeviceStatus::Notification => 0x05,
+            DeviceStatus::PhaseChange => 0x06,
+            DeviceStatus::Unknown(n) => n,
+        };
+        frame[19] = phase;
+        if self.media.as_ref().is_some_and(|m| m.two_colour) {
+            frame[25] = TextColour::BlackRed as u8;
+        }
+
+        frame
+    }
+}
+
+impl PTouchInterface for Emulator {
+    fn name(&self) -> String {
+        format!("Emulated {:?}", self.model)
+    }
+
+    /// Pending status replies, a `TimedOut` error when there are none
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        if self.output.is_empty() {
+            return Err(io::Error::from(io::ErrorKind::TimedOut).into());
+        }
+
+        let n = buf.len().min(self.output.len());
+        for (b, o) in buf.iter_mut().zip(self.output.drain(..n)) {
+            *b = o;
+        }
+
+        Ok(n)
+    }
+
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        Ok(self.output.drain(..).collect())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.feed(data);
+        Ok(())
+    }
+}
+
+/// Emulator served on a TCP port (like port 9100 of a network printer), one client at a time
+pub struct EmulatorServer {
+    addr: SocketAddr,
+    emulator: Arc<Mutex<Emulator>>,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl EmulatorServer {
+    /// Serve `emulator` on `addr`, use port 0 for a free port
+    pub fn bind<A: ToSocketAddrs>(addr: A, emulator: Emulator) -> Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let addr = listener.local_addr()?;
+        let emulator = Arc::new(Mutex::new(emulator));
+        let stop = Arc::new(AtomicBool::new(false));
+
+        let handle = thread::spawn({
+            let (emulator, stop) = (emulator.clone(), stop.clone());
+            move || {
+                for stream in listener.incoming() {
+                    if stop.load(Ordering::Relaxed) {
+                        break;
+                    }
+                    if let Ok(stream) = stream {
+                        // A client hanging up is not our problem
+                        let _ = serve_client(stream, &emulator);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            emulator,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Access the emulator, e.g. to inspect the printed pages or to simulate errors
+    pub fn emulator(&self) -> MutexGuard<'_, Emulator> {
+        self.emulator.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Answer SNMP requests for the served emulator on the UDP port `addr`
+    #[cfg(feature = "net")]
+    pub fn serve_snmp<A: ToSocketAddrs>(&self, addr: A) -> Result<SnmpAgent> {
+        SnmpAgent::bind(addr, self.emulator.clone())
+    }
+}
+
+impl Drop for EmulatorServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+
+        // Wake up the accept loop
+        if TcpStream::connect(self.addr).is_ok() {
+            if let Some(handle) = self.handle.take() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
+
+/// SNMP (v2c GET) agent of an emulated network printer, answers with the name, model, serial
+/// number, status, network settings and page counters of the emulator. Any community is accepted
+#[cfg(feature = "net")]
+pub struct SnmpAgent {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "net")]
+impl SnmpAgent {
+    fn bind<A: ToSocketAddrs>(addr: A, emulator: Arc<Mutex<Emulator>>) -> Result<Self> {
+        let socket = UdpSocket::bind(addr)?;
+        socket.set_read_timeout(Some(Duration::from_millis(100)))?;
+        let addr = socket.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let started = Instant::now();
+
+        let handle = thread::spawn({
+            let stop = stop.clone();
+            move || {
+                let mut buf = [0u8; 1500];
+                while !stop.load(Ordering::Relaxed) {
+                    let Ok((n, peer)) = socket.recv_from(&mut buf) else {
+                        continue;
+                    };
+
+                    let response = {
+                        let emulator = emulator.lock().unwrap_or_else(|e| e.into_inner());
+                        snmp_agent::respond(&emulator, addr, started.elapsed(), &buf[..n])
+                    };
+                    if let Some(response) = response {
+                        let _ = socket.send_to(&response, peer);
+                    }
+                }
+            }
+        });
+
+        Ok(Self {
+            addr,
+            stop,
+            handle: Some(handle),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+#[cfg(feature = "net")]
+impl Drop for SnmpAgent {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+/// Minimal SNMP responder, requests are parsed with the `snmp` crate, responses are BER encoded
+/// by hand as the crate has no encoder for them
+#[cfg(feature = "net")]
+mod snmp_agent {
+    use std::{
+        net::{IpAddr, SocketAddr},
+        time::Duration,
+    };
+
+    use snmp::{SnmpMessageType, SnmpPdu};
+
+    use super::Emulator;
+    use crate::{printer::snmp_oid, status::DeviceStatus};
+
+    const TYPE_INTEGER: u8 = 0x02;
+    const TYPE_OCTET_STRING: u8 = 0x04;
+    const TYPE_OID: u8 = 0x06;
+    const TYPE_SEQUENCE: u8 = 0x30;
+    const TYPE_IP_ADDRESS: u8 = 0x40;
+    const TYPE_COUNTER32: u8 = 0x41;
+    const TYPE_TIMETICKS: u8 = 0x43;
+    const MSG_RESPONSE: u8 = 0xa2;
+    const NO_SUCH_OBJECT: u8 = 0x80;
+
+    /// Response to a GET request, `None` for anything else
+    pub(super) fn respond(
+        emulator: &Emulator,
+        addr: SocketAddr,
+        uptime: Duration,
+        request: &[u8],
+    ) -> Option<Vec<u8>> {
+        let pdu = SnmpPdu::from_bytes(request).ok()?;
+        if pdu.message_type != SnmpMessageType::GetRequest {
+            return None;
+        }
+
+        let mut varbinds = Vec::new();
+        for (oid, _value) in pdu.varbinds {
+            let mut buf = [0; 128];
+            let name = oid.read_name(&mut buf).ok()?;
+            let value =
+                value(emulator, addr, uptime, name).unwrap_or_else(|| tlv(NO_SUCH_OBJECT, &[]));
+            varbinds.extend(tlv(TYPE_SEQUENCE, &[encode_oid(name), value].concat()));
+        }
+
+        let response = [
+            integer(pdu.req_id.into()),
+            integer(0), // error status
+            integer(0), // error index
+            tlv(TYPE_SEQUENCE, &varbinds),
+        ]
+        .concat();
+        let message = [
+            integer(1), // v2c
+            tlv(TYPE_OCTET_STRING, b"public"),
+            tlv(MSG_RESPONSE, &response),
+        ]
+        .concat();
+
+        Some(tlv(TYPE_SEQUENCE, &message))
+    }
+
+    fn value(
+        emulator: &Emulator,
+        addr: SocketAddr,
+        uptime: Duration,
+        oid: &[u32],
+    ) -> Option<Vec<u8>> {
+        let octets = |data: &[u8]| Some(tlv(TYPE_OCTET_STRING, data));
+        let model = format!("{:?}", emulator.model);
+        let [port_hi, port_lo] = addr.port().to_be_bytes();
+
+        let printing = emulator.pages_printing();
+
+        match oid {
+            snmp_oid::STATUS => {
+                octets(&emulator.status_frame(DeviceStatus::Reply, printing.min(1) as u8))
+            }
+            snmp_oid::NAME => octets(format!("BRN{:06X}", addr.port()).as_bytes()),
+            snmp_oid::MODEL => {
+                octets(format!("Brother {}-{}", &model[..2], &model[2..]).as_bytes())
+            }
+            snmp_oid::SERIAL => octets(format!("E{:08}", addr.port()).as_bytes()),
+            snmp_oid::IP_ADDR => match addr.ip() {
+                IpAddr::V4(ip) => Some(tlv(TYPE_IP_ADDRESS, &ip.octets())),
+                IpAddr::V6(_) => None,
+            },
+            snmp_oid::SUBNET => Some(tlv(TYPE_IP_ADDRESS, &[255, 255, 255, 0])),
+            snmp_oid::MAC => octets(&[0x00, 0x80, 0x92, 0x00, port_hi, port_lo]),
+            snmp_oid::UPTIME => Some(integer_with_tag(
+                TYPE_TIMETICKS,
+                (uptime.as_millis() / 10) as i64,
+            )),
+            // Printing or idle
+            snmp_oid::PRINTER_STATUS => Some(integer(if printing > 0 { 4 } else { 3 })),
+            snmp_oid::LIFE_COUNT | snmp_oid::POWER_ON_COUNT => Some(integer_with_tag(
+                TYPE_COUNTER32,
+                (emulator.page_count - printing) as i64,
+            )),
+            _ => None,
+        }
+    }
+
+    fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut data = vec![tag];
+        match content.len() {
+            len @ 0..=0x7f => data.push(len as u8),
+            len => {
+                let len_bytes = (len as u32).to_be_bytes();
+                let skip = len_bytes.iter().take_while(|&&b| b == 0).count();
+                data.push(0x80 | (4 - skip) as u8);
+                data.extend(&len_bytes[skip..]);
+            }
+        }
+        data.extend(content);
+        data
+    }
+
+    fn integer(value: i64) -> Vec<u8> {
+        integer_with_tag(TYPE_INTEGER, value)
+    }
+
+    /// Integer encoding, also used by the unsigned application types (counters, timeticks)
+    fn integer_with_tag(tag: u8, value: i64) -> Vec<u8> {
+        let bytes = value.to_be_bytes();
+        // Shortest two's complement form
+        let mut start = 0;
+        while start < 7
+            && ((bytes[start] == 0 && bytes[start + 1] & 0x80 == 0)
+                || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+        {
+            start += 1;
+        }
+        tlv(tag, &bytes[start..])
+    }
+
+    fn encode_oid(oid: &[u32]) -> Vec<u8> {
+        let mut data = vec![(oid[0] * 40 + oid[1]) as u8];
+        for &sub_id in &oid[2..] {
+            let mut chunk = vec![(sub_id & 0x7f) as u8];
+            let mut rest = sub_id >> 7;
+            while rest > 0 {
+                chunk.insert(0, 0x80 | (rest & 0x7f) as u8);
+                rest >>= 7;
+            }
+            data.extend(chunk);
+        }
+        tlv(TYPE_OID, &data)
+    }
+}
+
+fn serve_client(mut stream: TcpStream, emulator: &Mutex<Emulator>) -> io::Result<()> {
+    let mut buf = [0u8; 4096];
+
+    loop {
+        let n = stream.read(&mut buf)?;
+        if n == 0 {
+            return Ok(());
+        }
+
+        let reply: Vec<u8> = {
+            let mut emulator = emulator.lock().unwrap_or_else(|e| e.into_inner());
+            emulator.feed(&buf[..n]);
+            emulator.output.drain(..).collect()
+        };
+
+        stream.write_all(&reply)?;
+    }
+}
+
+#[test]
+fn emulated_print_job() {
+    use crate::{
+        job::PrintJob,
+        media,
+        prelude::*,
+        rasterize::{Align, Rasterizer},
+    };
+
+    let media = media::by_product("DK-22210").unwrap();
+    let emulator = Emulator::new(Model::QL810W, Some(media));
+    let mut printer = PTouchPrinter::from_interface(emulator);
+
+    let status = printer.get_status().unwrap();
+    assert_eq!(status.model, Model::QL810W);
+    assert_eq!(Media::from_status(&status), Some(media));
+
+    let image = image::DynamicImage::ImageLuma8(image::GrayImage::from_fn(20, 10, |x, _| {
+        image::Luma([if x < 5 { 0 } else { 255 }])
+    }));
+    let page = Rasterizer::new(media).align(Align::Left).rasterize(&image);
+    let job = PrintJob::new()
+        .media(media)
+        .page(page)
+        .copies(2)
+        .compression(CompressionMode::Tiff);
+    printer.print_job(&job).unwrap();
+
+    let pages = printer.interface.take_pages();
+    assert_eq!(pages.len(), 2);
+    assert!(!pages[0].feed && pages[1].feed);
+    assert_eq!((pages[0].raster_no, pages[0].lines.len()), (10, 10));
+
+    let rendered = pages[1].to_image();
+    let offset = media.print_area.offset;
+    assert_eq!(rendered.dimensions(), (720, 10));
+    assert_eq!(rendered.get_pixel(offset + 4, 9).0, [0, 0, 0]);
+    assert_eq!(rendered.get_pixel(offset + 5, 9).0, [255, 255, 255]);
+
+    // Phase change (printing), completed, phase change (waiting) for every page
+    let notifications = printer.interface.read_vec().unwrap();
+    let status_types: Vec<u8> = notifications.chunks(32).map(|frame| frame[18]).collect();
+    assert_eq!(status_types, [6, 1, 6, 6, 1, 6]);
+
+    // Wrong media and cover open make the print fail
+    printer.interface.set_media(media::by_product("DK-11201"));
+    printer.print_job(&job).unwrap();
+    let status = printer.read_status().unwrap();
+    assert_eq!(status.status_type, DeviceStatus::Error);
+    assert!(status.error_status.contains(ErrorStatus::WRONG_MEDIA));
+    assert!(printer.interface.pages().is_empty());
+
+    printer.interface.set_media(Some(media));
+    printer.interface.set_error(ErrorStatus::COVER_OPEN);
+    printer.interface.read_vec().unwrap();
+    let status = printer.get_status().unwrap();
+    assert!(status.error_status.contains(ErrorStatus::COVER_OPEN));
+}
+
+#[test]
+fn emulator_server() {
+    use crate::{job::PrintJob, media, prelude::*};
+
+    let media = media::by_product("DK-11201").unwrap();
+    let server =
+        EmulatorServer::bind("127.0.0.1:0", Emulator::new(Model::QL720NW, Some(media))).unwrap();
+
+    let mut printer = printer::from_addr(server.local_addr()).unwrap();
+    let status = printer.get_status().unwrap();
+    assert_eq!(status.model, Model::QL720NW);
+    assert_eq!((status.media_width, status.media_length), (29, 90));
+
+    let job = PrintJob::new().media(media).page(RasterBuffer::new(991));
+    printer.print_job(&job).unwrap();
+
+    // The printing completed notification tells us the page is done
+    let mut completed = false;
+    while !completed {
+        completed = printer.read_status().unwrap().status_type == DeviceStatus::Completed;
+    }
+
+    let emulator = server.emulator();
+    assert_eq!(emulator.pages().len(), 1);
+    assert_eq!(emulator.pages()[0].lines.len(), 991);
+}
@@ -1,10 +1,17 @@
 use std::{
-    io::{Read, Write},
+    io::{self, Read, Write},
     net::{IpAddr, TcpStream, ToSocketAddrs},
     time::Duration,
 };
 
-use crate::Result;
+#[cfg(unix)]
+use std::{
+    fs::{File, OpenOptions},
+    os::unix::io::{AsRawFd, RawFd},
+    path::{Path, PathBuf},
+};
+
+use crate::{PTouchError, Result};
 
 pub trait PTouchInterface: Sized {
     fn name(&self) -> String;
@@ -23,7 +30,32 @@ pub trait PTouchInterface: Sized {
 #[derive(Debug)]
 pub struct PTouchTcpInterface {
     socket: TcpStream,
+    /// Address for the SNMP queries
+    #[cfg_attr(not(feature = "net"), allow(dead_code))]
     pub(crate) ip_addr: Option<IpAddr>,
+    pub(crate) snmp: SnmpSettings,
+}
+
+/// SNMP session settings for the status and information queries of a `PTouchTcpInterface`
+#[derive(Clone, PartialEq, Debug)]
+pub struct SnmpSettings {
+    pub community: String,
+    pub port: u16,
+    /// Time to wait for a response, per attempt
+    pub timeout: Duration,
+    /// Number of times a request is repeated when there is no (valid) response
+    pub retries: u32,
+}
+
+impl Default for SnmpSettings {
+    fn default() -> Self {
+        Self {
+            community: "public".into(),
+            port: 161,
+            timeout: Duration::from_millis(500),
+            retries: 1,
+        }
+    }
 }
 
 impl PTouchTcpInterface {
@@ -33,8 +65,19 @@ impl PTouchTcpInterface {
         let socket = TcpStream::connect(addr)?;
         socket.set_read_timeout(read_timeout)?;
 
-        println!("sokket: {socket:?}");
-        Ok(PTouchTcpInterface { socket, ip_addr })
+        Ok(PTouchTcpInterface {
+            socket,
+            ip_addr,
+            snmp: SnmpSettings::default(),
+        })
+    }
+
+    pub fn snmp_settings(&self) -> &SnmpSettings {
+        &self.snmp
+    }
+
+    pub fn set_snmp_settings(&mut self, settings: SnmpSettings) {
+        self.snmp = settings;
     }
 }
 
@@ -69,3 +112,435 @@ impl PTouchInterface for PTouchTcpInterface {
         Ok(())
     }
 }
+
+/// Interface for USB printer class devices, e.g. `/dev/usb/lp0` on Linux.
+/// Any character device (or FIFO) path can be used.
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct PTouchUsbLpInterface {
+    device: File,
+    path: PathBuf,
+    read_timeout: Option<Duration>,
+}
+
+#[cfg(unix)]
+impl PTouchUsbLpInterface {
+    pub fn new<P: AsRef<Path>>(path: P, read_timeout: Option<Duration>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let device = OpenOptions::new().read(true).write(true).open(&path)?;
+
+        Ok(PTouchUsbLpInterface {
+            device,
+            path,
+            read_timeout,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl PTouchInterface for PTouchUsbLpInterface {
+    fn name(&self) -> String {
+        format!("PTouch USB interface on {}", self.path.display())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        poll_readable(self.device.as_raw_fd(), self.read_timeout)?;
+        Ok(self.device.read(buf)?)
+    }
+
+    /// Read until the device has no more data for us (i.e. EOF or the read timeout expires)
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        read_until_idle(self)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.device.write_all(data)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.device.flush()?;
+        Ok(())
+    }
+}
+
+/// Flow control used on a serial link
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub enum FlowControl {
+    #[default]
+    None,
+    /// RTS/CTS
+    Hardware,
+    /// XON/XOFF
+    Software,
+}
+
+/// Serial port settings for the `PTouchSerialInterface`
+#[derive(Clone, PartialEq, Debug)]
+pub struct SerialSettings {
+    pub baud_rate: u32,
+    pub flow_control: FlowControl,
+    pub read_timeout: Option<Duration>,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        Self {
+            baud_rate: 9600,
+            flow_control: FlowControl::None,
+            read_timeout: Some(Duration::from_millis(1500)),
+        }
+    }
+}
+
+/// Interface for serial tty devices, e.g. Bluetooth SPP (`/dev/rfcomm0`) or USB-serial adapters
+#[cfg(unix)]
+#[derive(Debug)]
+pub struct PTouchSerialInterface {
+    tty: File,
+    path: PathBuf,
+    read_timeout: Option<Duration>,
+}
+
+#[cfg(unix)]
+impl PTouchSerialInterface {
+    pub fn new<P: AsRef<Path>>(path: P, settings: &SerialSettings) -> Result<Self> {
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let path = path.as_ref().to_path_buf();
+        let tty = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&path)?;
+
+        configure_tty(tty.as_raw_fd(), settings)?;
+
+        Ok(PTouchSerialInterface {
+            tty,
+            path,
+            read_timeout: settings.read_timeout,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(unix)]
+impl PTouchInterface for PTouchSerialInterface {
+    fn name(&self) -> String {
+        format!("PTouch serial interface on {}", self.path.display())
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        poll_readable(self.tty.as_raw_fd(), self.read_timeout)?;
+        Ok(self.tty.read(buf)?)
+    }
+
+    /// Read until the device has no more data for us (i.e. EOF or the read timeout expires)
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        read_until_idle(self)
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.tty.write_all(data)?;
+        Ok(())
+    }
+
+    /// Blocks until all written data has actually been transmitted
+    fn flush(&mut self) -> Result<()> {
+        // SAFETY: the fd is owned by `self.tty` and open for the duration of the call
+        if unsafe { libc::tcdrain(self.tty.as_raw_fd()) } != 0 {
+            return Err(io::Error::last_os_error().into());
+        }
+
+        Ok(())
+    }
+}
+
+/// Put the tty in raw mode with the requested line settings
+#[cfg(unix)]
+fn configure_tty(fd: RawFd, settings: &SerialSettings) -> io::Result<()> {
+    let speed = match settings.baud_rate {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        baud => {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("unsupported baud rate {baud}"),
+            ))
+        }
+    };
+
+    // SAFETY: termios is a plain C struct, all-zero is a valid value and it is fully
+    // initialised by `tcgetattr` before use
+    let mut tio: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut tio) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    // SAFETY: `tio` is a valid termios struct obtained from `tcgetattr`
+    unsafe {
+        libc::cfmakeraw(&mut tio);
+        libc::cfsetispeed(&mut tio, speed);
+        libc::cfsetospeed(&mut tio, speed);
+    }
+
+    tio.c_cflag |= libc::CLOCAL | libc::CREAD;
+    tio.c_cflag &= !libc::CRTSCTS;
+    tio.c_iflag &= !(libc::IXON | libc::IXOFF | libc::IXANY);
+    match settings.flow_control {
+        FlowControl::None => {}
+        FlowControl::Hardware => tio.c_cflag |= libc::CRTSCTS,
+        FlowControl::Software => tio.c_iflag |= libc::IXON | libc::IXOFF,
+    }
+
+    // Reads are gated by `poll_readable`, so a plain blocking read of at least one byte
+    tio.c_cc[libc::VMIN] = 1;
+    tio.c_cc[libc::VTIME] = 0;
+
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &tio) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+/// Keep reading until EOF, or until a read times out after some data has been received
+#[cfg(unix)]
+fn read_until_idle<I: PTouchInterface>(interface: &mut I) -> Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 64];
+
+    loop {
+        match interface.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+            Err(PTouchError::IoError(e))
+                if e.kind() == io::ErrorKind::TimedOut && !buf.is_empty() =>
+            {
+                break
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(buf)
+}
+
+/// Block until `fd` is readable, returns a `TimedOut` error when `timeout` expires first
+#[cfg(unix)]
+fn poll_readable(fd: RawFd, timeout: Option<Duration>) -> io::Result<()> {
+    let timeout_ms = timeout
+        .map(|t| t.as_millis().min(i32::MAX as u128) as i32)
+        .unwrap_or(-1);
+
+    let mut pollfd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+
+    loop {
+        // SAFETY: `pollfd` is a valid, exclusively borrowed pollfd struct and nfds is 1
+        match unsafe { libc::poll(&mut pollfd, 1, timeout_ms) } {
+            -1 => {
+                let err = io::Error::last_os_error();
+                if err.kind() != io::ErrorKind::Interrupted {
+                    return Err(err);
+                }
+            }
+            0 => return Err(io::Error::new(io::ErrorKind::TimedOut, "read timed out")),
+            _ => return Ok(()),
+        }
+    }
+}
+
+/// Capture interface, writes the command stream to any `Write` (file, `Vec<u8>`, stdout) so jobs
+/// can be produced without a printer attached. There is no printer to reply, reads return no data.
+#[derive(Debug)]
+pub struct PTouchWriteInterface<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PTouchWriteInterface<W> {
+    pub fn new(writer: W) -> Self {
+        PTouchWriteInterface { writer }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.writer
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W: Write> PTouchInterface for PTouchWriteInterface<W> {
+    fn name(&self) -> String {
+        "PTouch capture interface".to_string()
+    }
+
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+        Ok(0)
+    }
+
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        Ok(Vec::new())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.writer.write_all(data)?;
+        Ok(())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+/// In-memory interface for tests, records everything written and replays `reply` on reads
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub(crate) struct TestInterface {
+    pub sent: Vec<u8>,
+    pub reply: std::collections::VecDeque<u8>,
+}
+
+#[cfg(test)]
+impl PTouchInterface for TestInterface {
+    fn name(&self) -> String {
+        "test interface".into()
+    }
+
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        // Hand out the reply in small chunks, like a slow link would
+        let n = buf.len().min(self.reply.len()).min(5);
+        for b in buf.iter_mut().take(n) {
+            *b = self.reply.pop_front().unwrap();
+        }
+        Ok(n)
+    }
+
+    fn read_vec(&mut self) -> Result<Vec<u8>> {
+        Ok(self.reply.drain(..).collect())
+    }
+
+    fn write(&mut self, data: &[u8]) -> Result<()> {
+        self.sent.extend_from_slice(data);
+        Ok(())
+    }
+}
+
+#[cfg(unix)]
+#[test]
+fn usb_lp_interface_on_fifo() {
+    use std::ffi::CString;
+
+    let path = std::env::temp_dir().join(format!("ql-raster-usblp-{}", std::process::id()));
+    let c_path = CString::new(path.to_str().unwrap()).unwrap();
+    // SAFETY: `c_path` is a valid nul-terminated string
+    assert_eq!(unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) }, 0);
+
+    // A FIFO opened read/write loops our own writes back, standing in for the printer replies
+    let mut lp = PTouchUsbLpInterface::new(&path, Some(Duration::from_millis(100))).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let mut buf = [0u8; 4];
+    assert!(matches!(
+        lp.read(&mut buf),
+        Err(PTouchError::IoError(e)) if e.kind() == io::ErrorKind::TimedOut
+    ));
+
+    lp.write(&[0x1b, 0x69, 0x53]).unwrap();
+    lp.flush().unwrap();
+    assert_eq!(lp.read(&mut buf).unwrap(), 3);
+    assert_eq!(&buf[..3], &[0x1b, 0x69, 0x53]);
+
+    lp.write(&[0x80; 32]).unwrap();
+    assert_eq!(lp.read_vec().unwrap(), vec![0x80; 32]);
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn serial_interface_on_pty() {
+    use std::{ffi::CStr, os::unix::io::FromRawFd};
+
+    // SAFETY: plain libc pty setup, every return value is checked
+    let (mut master, slave_path) = unsafe {
+        let fd = libc::posix_openpt(libc::O_RDWR | libc::O_NOCTTY);
+        assert!(fd >= 0);
+        assert_eq!(libc::grantpt(fd), 0);
+        assert_eq!(libc::unlockpt(fd), 0);
+
+        let mut name = [0 as libc::c_char; 64];
+        assert_eq!(libc::ptsname_r(fd, name.as_mut_ptr(), name.len()), 0);
+        let path = CStr::from_ptr(name.as_ptr()).to_str().unwrap().to_owned();
+
+        (File::from_raw_fd(fd), path)
+    };
+
+    let settings = SerialSettings {
+        baud_rate: 115200,
+        read_timeout: Some(Duration::from_millis(100)),
+        ..Default::default()
+    };
+    let mut tty = PTouchSerialInterface::new(&slave_path, &settings).unwrap();
+
+    let mut buf = [0u8; 8];
+    assert!(matches!(
+        tty.read(&mut buf),
+        Err(PTouchError::IoError(e)) if e.kind() == io::ErrorKind::TimedOut
+    ));
+
+    // Raw mode, so no line discipline mangling of the binary payload
+    tty.write(&[0x1b, 0x69, 0x53, 0x0a, 0x0d]).unwrap();
+    tty.flush().unwrap();
+    let mut sent = [0u8; 5];
+    master.read_exact(&mut sent).unwrap();
+    assert_eq!(sent, [0x1b, 0x69, 0x53, 0x0a, 0x0d]);
+
+    master.write_all(&[0x80; 32]).unwrap();
+    assert_eq!(tty.read_vec().unwrap(), vec![0x80; 32]);
+
+    let bad = SerialSettings {
+        baud_rate: 12345,
+        ..Default::default()
+    };
+    assert!(PTouchSerialInterface::new(&slave_path, &bad).is_err());
+}
+
+#[test]
+fn capture_and_replay() {
+    use crate::{emulator::Emulator, job::PrintJob, media, prelude::*, status::Model};
+
+    let media = media::by_product("DK-22205").unwrap();
+    let job = PrintJob::new().media(media).page(RasterBuffer::new(10));
+
+    let mut printer = printer::from_writer(Vec::new());
+    printer.print_job(&job).unwrap();
+    let prn = printer.interface.into_inner();
+
+    let mut expected = PTouchPrinter::from_interface(TestInterface::default());
+    expected.print_job(&job).unwrap();
+    assert_eq!(prn, expected.interface.sent);
+
+    let mut printer = PTouchPrinter::from_interface(Emulator::new(Model::QL800, Some(media)));
+    assert_eq!(printer.replay(prn.as_slice()).unwrap(), prn.len() as u64);
+    assert_eq!(printer.interface.pages().len(), 1);
+}
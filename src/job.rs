@@ -0,0 +1,935 @@
+//! High-level print job, emits the complete command sequence for one or more pages.
+//!
+//! ```no_run
+//! use ql_raster::{job::PrintJob, prelude::*};
+//!
+//! # fn main() -> Result<()> {
+//! let mut printer = printer::from_addr("labelprinter:9100")?;
+//! let media = Media::from_status(&printer.get_status()?).expect("Unknown media");
+//!
+//! let job = PrintJob::new().media(media).page(RasterBuffer::new(300)).copies(2);
+//! printer.print_job(&job)
+//! # }
+//! ```
+
+use std::{
+    fmt::{self, Display, Formatter},
+    io, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    commands::{AdvancedMode, Command, CompressionMode, Mode},
+    prelude::*,
+    printer::Protocol,
+    status::{Capabilities, DeviceStatus, ErrorStatus, MediaKind, Model},
+    PTouchError,
+};
+
+/// Pause between status reads (or SNMP polls) while waiting for a job
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Auto cut behaviour
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum CutMode {
+    /// No auto cut
+    None,
+    /// Cut after every label
+    #[default]
+    EachLabel,
+    /// Cut after every n labels
+    Every(u8),
+}
+
+/// Result of a print job as reported by the printer
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum JobOutcome {
+    /// All pages printed, not cut
+    Printed,
+    /// All pages printed and cut
+    Cut,
+    /// Printing stopped, with the errors reported by the printer
+    Failed(ErrorStatus),
+}
+
+/// Reason a job does not fit the printer, found before sending it
+#[derive(Clone, PartialEq, Debug)]
+pub enum JobError {
+    /// The printer reports no media loaded
+    NoMedia,
+    /// The media loaded in the printer differs from the media of the job
+    WrongMedia {
+        needs: Media,
+        kind: MediaKind,
+        width: u8,
+        length: u8,
+    },
+    /// Page raster lines are not sized for the print engine
+    WrongEngine {
+        page: usize,
+        needs: Engine,
+        has: Engine,
+    },
+    /// Page is longer than a die-cut label
+    PageTooLong {
+        page: usize,
+        lines: usize,
+        max: usize,
+    },
+    /// Two-colour page for single colour media
+    NotTwoColour { page: usize, media: Media },
+    /// The printer model lacks a feature the job needs
+    Unsupported { model: Model, feature: &'static str },
+}
+
+impl Display for JobError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoMedia => write!(f, "printer has no media loaded"),
+            Self::WrongMedia {
+                needs,
+                kind,
+                width,
+                length,
+            } => {
+                write!(f, "job needs {needs}, printer has ")?;
+                match crate::media::lookup(*kind, *width, *length) {
+                    Some(media) => write!(f, "{media}"),
+                    None if *length == 0 => write!(f, "{width}mm {kind:?}"),
+                    None => write!(f, "{width}x{length}mm {kind:?}"),
+                }
+            }
+            Self::WrongEngine { page, needs, has } => write!(
+                f,
+                "page {page} has {} dot lines, printer needs {} dots",
+                has.dots(),
+                needs.dots()
+            ),
+            Self::PageTooLong { page, lines, max } => write!(
+                f,
+                "page {page} has {lines} lines, the label fits {max} lines"
+            ),
+            Self::NotTwoColour { page, media } => {
+                write!(f, "page {page} is two-colour, {media} is not")
+            }
+            Self::Unsupported { model, feature } => {
+                write!(f, "{model:?} does not support {feature}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for JobError {}
+
+/// Single page of a print job
+pub enum Page {
+    Mono(RasterBuffer),
+    TwoColour(TwoColourRasterBuffer),
+}
+
+impl Page {
+    /// Number of raster lines, a two-colour line pair counts as one
+    pub fn height(&self) -> usize {
+        match self {
+            Self::Mono(buffer) => buffer.len(),
+            Self::TwoColour(buffer) => buffer.height(),
+        }
+    }
+
+    pub fn engine(&self) -> Engine {
+        match self {
+            Self::Mono(buffer) => buffer.engine(),
+            Self::TwoColour(buffer) => buffer.black.engine(),
+        }
+    }
+}
+
+impl From<RasterBuffer> for Page {
+    fn from(buffer: RasterBuffer) -> Self {
+        Self::Mono(buffer)
+    }
+}
+
+impl From<TwoColourRasterBuffer> for Page {
+    fn from(buffer: TwoColourRasterBuffer) -> Self {
+        Self::TwoColour(buffer)
+    }
+}
+
+/// Print job builder
+pub struct PrintJob {
+    pages: Vec<Page>,
+    media: Option<Media>,
+    cut: CutMode,
+    cut_at_end: bool,
+    half_cut: bool,
+    copies: u16,
+    margin: Option<u16>,
+    high_res: bool,
+    compression: CompressionMode,
+}
+
+impl Default for PrintJob {
+    fn default() -> Self {
+        Self {
+            pages: Vec::new(),
+            media: None,
+            cut: CutMode::default(),
+            cut_at_end: true,
+            half_cut: false,
+            copies: 1,
+            margin: None,
+            high_res: false,
+            compression: CompressionMode::None,
+        }
+    }
+}
+
+impl PrintJob {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append a page
+    pub fn page(mut self, page: impl Into<Page>) -> Self {
+        self.pages.push(page.into());
+        self
+    }
+
+    /// Media to print on, sets the print information and the default feed margin
+    pub fn media(mut self, media: &Media) -> Self {
+        self.media = Some(media.clone());
+        self
+    }
+
+    pub fn cut(mut self, cut: CutMode) -> Self {
+        self.cut = cut;
+        self
+    }
+
+    /// Cut after the last page, regardless of the cut mode (default on)
+    pub fn cut_at_end(mut self, cut_at_end: bool) -> Self {
+        self.cut_at_end = cut_at_end;
+        self
+    }
+
+    /// Cut the tape but not the backing paper, on tape printers with a half cutter
+    pub fn half_cut(mut self, half_cut: bool) -> Self {
+        self.half_cut = half_cut;
+        self
+    }
+
+    /// Print all pages `copies` times (collated)
+    pub fn copies(mut self, copies: u16) -> Self {
+        self.copies = copies;
+        self
+    }
+
+    /// Pre/post print feed margin in dots, overrides the margin of the media
+    pub fn margin(mut self, dots: u16) -> Self {
+        self.margin = Some(dots);
+        self
+    }
+
+    /// Print at double resolution in the feed direction, pages need twice the lines
+    pub fn high_res(mut self, high_res: bool) -> Self {
+        self.high_res = high_res;
+        self
+    }
+
+    pub fn compression(mut self, mode: CompressionMode) -> Self {
+        self.compression = mode;
+        self
+    }
+
+    pub fn pages(&self) -> &[Page] {
+        &self.pages
+    }
+
+    pub fn get_media(&self) -> Option<&Media> {
+        self.media.as_ref()
+    }
+
+    /// Number of pages printed, including copies
+    pub fn page_count(&self) -> usize {
+        self.pages.len() * self.copies as usize
+    }
+
+    /// Does the printer cut the labels, after every n labels or at the end
+    pub fn cuts(&self) -> bool {
+        self.cut != CutMode::None || self.cut_at_end
+    }
+
+    /// Check the job against the media loaded in the printer and the page sizes against the
+    /// print engine and media
+    pub fn validate(&self, status: &Status) -> std::result::Result<(), JobError> {
+        if status.media_kind == MediaKind::None || status.media_width == 0 {
+            return Err(JobError::NoMedia);
+        }
+
+        if let Some(media) = &self.media {
+            let loaded = Media::from_status(status);
+            let matches = loaded.is_some_and(|m| {
+                (m.form, m.width, m.length, m.two_colour)
+                    == (media.form, media.width, media.length, media.two_colour)
+            });
+
+            if !matches {
+                return Err(JobError::WrongMedia {
+                    needs: media.clone(),
+                    kind: status.media_kind,
+                    width: status.media_width,
+                    length: status.media_length,
+                });
+            }
+        }
+
+        let capabilities = status.model.capabilities();
+        let engine = capabilities
+            .map(|c| c.engine)
+            .or(self.media.as_ref().map(|m| m.engine));
+
+        if let Some(capabilities) = &capabilities {
+            let unsupported = |feature| JobError::Unsupported {
+                model: status.model,
+                feature,
+            };
+            let two_colour = self.pages.iter().any(|p| matches!(p, Page::TwoColour(_)));
+
+            if two_colour && !capabilities.two_colour {
+                return Err(unsupported("two-colour printing"));
+            }
+            if self.high_res && !capabilities.high_res {
+                return Err(unsupported("high resolution printing"));
+            }
+            if self.half_cut && !capabilities.half_cut {
+                return Err(unsupported("half cut"));
+            }
+        }
+
+        for (n, page) in self.pages.iter().enumerate() {
+            let page_no = n + 1;
+
+            if let Some(capabilities) = &capabilities {
+                let max = capabilities.max_lines() as usize * if self.high_res { 2 } else { 1 };
+                if page.height() > max {
+                    return Err(JobError::PageTooLong {
+                        page: page_no,
+                        lines: page.height(),
+                        max,
+                    });
+                }
+            }
+
+            if let Some(engine) = engine.filter(|&e| e != page.engine()) {
+                return Err(JobError::WrongEngine {
+                    page: page_no,
+                    needs: engine,
+                    has: page.engine(),
+                });
+            }
+
+            let Some(media) = &self.media else {
+                continue;
+            };
+
+            if let Some(label_length) = media.label_length {
+                let max = label_length as usize * if self.high_res { 2 } else { 1 };
+                if page.height() > max {
+                    return Err(JobError::PageTooLong {
+                        page: page_no,
+                        lines: page.height(),
+                        max,
+                    });
+                }
+            }
+
+            if matches!(page, Page::TwoColour(_)) && !media.two_colour {
+                return Err(JobError::NotTwoColour {
+                    page: page_no,
+                    media: media.clone(),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the printer reports no error (`PTouchError::Printer`) and the job fits the loaded
+    /// media, see `validate`
+    pub fn check(&self, status: &Status) -> Result<()> {
+        if !status.error_status.is_empty() {
+            return Err(PTouchError::Printer(status.error_status));
+        }
+
+        Ok(self.validate(status)?)
+    }
+
+    /// Outcome once every page is printed, as encoded for a printer with `capabilities`
+    pub(crate) fn outcome(&self, capabilities: Option<&Capabilities>) -> JobOutcome {
+        match self.page_settings(capabilities).cuts {
+            true => JobOutcome::Cut,
+            false => JobOutcome::Printed,
+        }
+    }
+
+    /// Command sequence of the whole job for `engine`, empty when there is nothing to print
+    pub fn commands(&self, engine: Engine) -> Vec<Command> {
+        self.build(engine, None)
+    }
+
+    /// Command sequence for a printer with `capabilities`, unsupported compression and auto
+    /// cut are left out
+    pub fn commands_for(&self, capabilities: &Capabilities) -> Vec<Command> {
+        self.build(capabilities.engine, Some(capabilities))
+    }
+
+    /// Commands as emitted by `send` to a printer with `engine` and `capabilities`
+    fn build(&self, engine: Engine, capabilities: Option<&Capabilities>) -> Vec<Command> {
+        let mut recorder = CommandRecorder {
+            protocol: Protocol::new(None),
+            commands: Vec::new(),
+        };
+        recorder.protocol.set_engine(engine);
+        if let Some(capabilities) = capabilities {
+            recorder.protocol.set_capabilities(*capabilities);
+        }
+
+        self.send(&mut recorder)
+            .expect("Recording the commands can't fail");
+        recorder.commands
+    }
+
+    /// Settings of every page, leaving out auto cut when the printer has no cutter
+    fn page_settings(&self, capabilities: Option<&Capabilities>) -> PageSettings {
+        let auto_cut = capabilities.is_none_or(|c| c.auto_cut);
+        let (various_mode, page_no) = match self.cut {
+            CutMode::None => (VariousMode::empty(), None),
+            _ if !auto_cut => (VariousMode::empty(), None),
+            CutMode::EachLabel => (VariousMode::AUTO_CUT, Some(1)),
+            CutMode::Every(n) => (VariousMode::AUTO_CUT, Some(n.max(1))),
+        };
+
+        let mut advanced_mode = AdvancedMode::empty();
+        // Same bit as 'cut at end' on the QL series, models without chain printing always
+        // feed out the last label
+        let chain_printing = capabilities.is_none_or(|c| c.chain_printing);
+        advanced_mode.set(AdvancedMode::NO_CHAIN, self.cut_at_end && chain_printing);
+        advanced_mode.set(
+            AdvancedMode::HALF_CUT,
+            self.half_cut && capabilities.is_none_or(|c| c.half_cut),
+        );
+        advanced_mode.set(AdvancedMode::HIGH_RES, self.high_res);
+        let cuts = auto_cut && (various_mode.contains(VariousMode::AUTO_CUT) || self.cut_at_end);
+
+        PageSettings {
+            print_info: self
+                .media
+                .as_ref()
+                .map(Media::print_info)
+                .unwrap_or_default(),
+            various_mode,
+            page_no,
+            advanced_mode,
+            margin: self
+                .margin
+                .or(self.media.as_ref().map(|m| m.feed_margin))
+                .unwrap_or(0),
+            cuts,
+        }
+    }
+
+    /// Emit the command sequence for the whole job using the `Commands` of `printer`, which
+    /// takes care of the raster line framing and compression
+    pub fn send(&self, printer: &mut impl Commands) -> Result<()> {
+        let total = self.page_count();
+        if total == 0 {
+            return Ok(());
+        }
+
+        let settings = self.page_settings(printer.capabilities().as_ref());
+        printer.invalidate()?;
+        printer.init()?;
+
+        for (n, page) in self.pages.iter().cycle().take(total).enumerate() {
+            printer.switch_mode(Mode::Raster)?;
+            printer.set_print_info(&settings.print_info(n, page))?;
+            printer.set_various_mode(settings.various_mode)?;
+            if let Some(page_no) = settings.page_no {
+                printer.set_page_no(page_no)?;
+            }
+            printer.set_advanced_mode(settings.advanced_mode(page))?;
+            printer.set_margin(settings.margin)?;
+            printer.set_compression_mode(self.compression)?;
+
+            match page {
+                Page::Mono(buffer) => {
+                    for line in buffer.iter() {
+                        printer.transfer_raster_line(line)?;
+                    }
+                }
+                Page::TwoColour(buffer) => {
+                    for (black, red) in buffer.iter() {
+                        printer.transfer_two_colour_raster_line(black, red)?;
+                    }
+                }
+            }
+
+            match n + 1 == total {
+                true => printer.print_and_feed()?,
+                false => printer.print()?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Per page settings of a job, shared by `PrintJob::commands` and `PrintJob::send`
+struct PageSettings {
+    print_info: PrintInfo,
+    various_mode: VariousMode,
+    page_no: Option<u8>,
+    advanced_mode: AdvancedMode,
+    margin: u16,
+    /// The printer cuts the labels with these settings
+    cuts: bool,
+}
+
+impl PageSettings {
+    /// Print information of page `n` (counting from 0, including copies)
+    fn print_info(&self, n: usize, page: &Page) -> PrintInfo {
+        PrintInfo {
+            raster_no: page.height() as u32,
+            first_page: n == 0,
+            ..self.print_info.clone()
+        }
+    }
+
+    fn advanced_mode(&self, page: &Page) -> AdvancedMode {
+        let mut advanced_mode = self.advanced_mode;
+        advanced_mode.set(AdvancedMode::TWO_COLOUR, matches!(page, Page::TwoColour(_)));
+        advanced_mode
+    }
+}
+
+/// Collects the commands of `PrintJob::send` instead of encoding them, framed and compressed
+/// by the same `Protocol` as the printers
+struct CommandRecorder {
+    protocol: Protocol,
+    commands: Vec<Command>,
+}
+
+impl CommandRecorder {
+    fn push(&mut self, command: Command) -> Result<()> {
+        self.protocol.track(&command);
+        self.commands.push(command);
+        Ok(())
+    }
+}
+
+impl Commands for CommandRecorder {
+    fn null(&mut self) -> Result<()> {
+        self.push(Command::Invalidate(1))
+    }
+
+    fn init(&mut self) -> Result<()> {
+        self.push(Command::Init)
+    }
+
+    fn invalidate(&mut self) -> Result<()> {
+        self.push(Command::Invalidate(400))
+    }
+
+    fn status_req(&mut self) -> Result<()> {
+        self.push(Command::StatusRequest)
+    }
+
+    fn read_status(&mut self) -> Result<Status> {
+        Err(io::Error::from(io::ErrorKind::Unsupported).into())
+    }
+
+    fn set_status_notify(&mut self, enabled: bool) -> Result<()> {
+        self.push(Command::StatusNotify(enabled))
+    }
+
+    fn switch_mode(&mut self, mode: Mode) -> Result<()> {
+        self.push(Command::SwitchMode(mode))
+    }
+
+    fn set_print_info(&mut self, info: &PrintInfo) -> Result<()> {
+        self.push(Command::PrintInfo(info.clone()))
+    }
+
+    fn set_various_mode(&mut self, mode: VariousMode) -> Result<()> {
+        self.push(Command::VariousMode(mode))
+    }
+
+    fn set_advanced_mode(&mut self, mode: AdvancedMode) -> Result<()> {
+        self.push(Command::AdvancedMode(mode))
+    }
+
+    fn set_margin(&mut self, dots: u16) -> Result<()> {
+        self.push(Command::Margin(dots))
+    }
+
+    fn set_page_no(&mut self, no: u8) -> Result<()> {
+        self.push(Command::PageNo(no))
+    }
+
+    fn set_compression_mode(&mut self, mode: CompressionMode) -> Result<()> {
+        let command = self.protocol.compression_mode(mode);
+        self.push(command)
+    }
+
+    fn transfer_raster_line(&mut self, data: &[u8]) -> Result<()> {
+        let command = self.protocol.raster_line(data);
+        self.push(command)
+    }
+
+    fn transfer_two_colour_raster_line(&mut self, black: &[u8], red: &[u8]) -> Result<()> {
+        let [black, red] = self.protocol.two_colour_raster_line(black, red);
+        self.push(black)?;
+        self.push(red)
+    }
+
+    fn raster_zero(&mut self) -> Result<()> {
+        self.push(Command::RasterZero)
+    }
+
+    fn print(&mut self) -> Result<()> {
+        self.push(Command::Print)
+    }
+
+    fn print_and_feed(&mut self) -> Result<()> {
+        self.push(Command::PrintAndFeed)
+    }
+
+    fn capabilities(&self) -> Option<Capabilities> {
+        self.protocol.capabilities()
+    }
+}
+
+impl<D: PTouchInterface> PTouchPrinter<D> {
+    /// Send a complete print job to the printer, without checking it against the loaded media
+    /// so it also works for interfaces that can't answer a status request (e.g. capturing to a
+    /// file). Use `preflight` or `print_job_and_wait` to validate the job first
+    pub fn print_job(&mut self, job: &PrintJob) -> Result<()> {
+        job.send(self)?;
+        self.flush()
+    }
+
+    /// Read the status and check the job against it, see `PrintJob::check`
+    pub fn preflight(&mut self, job: &PrintJob) -> Result<Status> {
+        let status = self.get_status()?;
+        job.check(&status)?;
+
+        Ok(status)
+    }
+
+    /// Check the job against the printer, send it and wait for the printer to finish it.
+    /// See `preflight` and `wait_for_job`
+    pub fn print_job_and_wait(&mut self, job: &PrintJob, timeout: Duration) -> Result<JobOutcome> {
+        self.preflight(job)?;
+        self.print_job(job)?;
+        self.wait_for_job(job, timeout)
+    }
+
+    /// Follow the status notifications on the data channel until every page of `job` is
+    /// printed or the printer reports an error. Fails with `PTouchError::Timeout` when the
+    /// job is not done within `timeout`
+    pub fn wait_for_job(&mut self, job: &PrintJob, timeout: Duration) -> Result<JobOutcome> {
+        let deadline = Instant::now() + timeout;
+        let mut tracker = NotificationTracker::default();
+        if let Some(outcome) = tracker.outcome(job, self.capabilities().as_ref()) {
+            return Ok(outcome);
+        }
+
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(PTouchError::Timeout);
+            }
+
+            let status = match self.read_status() {
+                Ok(status) => status,
+                // Nothing to report yet
+                Err(PTouchError::IoError(e))
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock
+                    ) =>
+                {
+                    thread::sleep(POLL_INTERVAL.min(deadline - now));
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if let Some(outcome) = tracker.update(job, &status) {
+                return Ok(outcome);
+            }
+        }
+    }
+}
+
+/// Follows a job through the status notifications on the data channel, the IO free part of
+/// `wait_for_job` shared with the async printer
+#[derive(Default)]
+pub(crate) struct NotificationTracker {
+    completed: usize,
+}
+
+impl NotificationTracker {
+    /// Outcome of `job` once every page is printed, on a printer with `capabilities`
+    pub(crate) fn outcome(
+        &self,
+        job: &PrintJob,
+        capabilities: Option<&Capabilities>,
+    ) -> Option<JobOutcome> {
+        (self.completed >= job.page_count()).then(|| job.outcome(capabilities))
+    }
+
+    /// Account for a status notification, an error ends the job right away
+    pub(crate) fn update(&mut self, job: &PrintJob, status: &Status) -> Option<JobOutcome> {
+        match status.status_type {
+            DeviceStatus::Error => Some(JobOutcome::Failed(status.error_status)),
+            DeviceStatus::Completed => {
+                self.completed += 1;
+                self.outcome(job, status.model.capabilities().as_ref())
+            }
+            _ => None,
+        }
+    }
+}
+
+/// SNMP status needs a model with a network interface, e.g. a USB model behind a print server
+/// doesn't answer the Brother MIB
+#[cfg(feature = "net")]
+pub(crate) fn check_snmp(status: &Status) -> Result<()> {
+    match status.model.capabilities() {
+        Some(capabilities) if !capabilities.network => Err(JobError::Unsupported {
+            model: status.model,
+            feature: "SNMP status",
+        }
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(feature = "net")]
+impl PTouchPrinter<PTouchTcpInterface> {
+    /// Like `print_job_and_wait`, polling the status and page counter over SNMP instead of
+    /// following the notifications on the data channel, see `SnmpMonitor::wait_for_job`
+    pub fn print_job_and_wait_snmp(
+        &mut self,
+        job: &PrintJob,
+        timeout: Duration,
+    ) -> Result<JobOutcome> {
+        check_snmp(&self.preflight(job)?)?;
+        let monitor = self.snmp_monitor()?;
+        let start_count = monitor.get_page_count()?;
+        self.print_job(job)?;
+        monitor.wait_for_job(job, start_count, timeout)
+    }
+}
+
+#[test]
+fn print_job_sequence() {
+    use crate::{interface::TestInterface, media};
+
+    let media = media::by_product("DK-11201").unwrap();
+    let job = PrintJob::new()
+        .media(media)
+        .page(RasterBuffer::new(2))
+        .page(RasterBuffer::new(3))
+        .copies(2)
+        .cut(CutMode::Every(2))
+        .compression(CompressionMode::Tiff);
+
+    let mut printer = PTouchPrinter::from_interface(TestInterface::default());
+    printer.print_job(&job).unwrap();
+
+    let sent = printer.interface.sent.as_slice();
+    assert!(sent[..400].iter().all(|&b| b == 0));
+    assert_eq!(sent[400..402], [0x1b, 0x40]);
+
+    let page_header = |raster_no: u8, first_page: u8| {
+        let mut header = vec![0x1b, 0x69, 0x61, 0x01];
+        header.extend([
+            0x1b, 0x69, 0x7a, 0x8e, 0x4b, 29, 90, raster_no, 0, 0, 0, first_page, 0,
+        ]);
+        header.extend([0x1b, 0x69, 0x4d, 0x40, 0x1b, 0x69, 0x41, 0x02]);
+        header.extend([
+            0x1b, 0x69, 0x4b, 0x08, 0x1b, 0x69, 0x64, 0x00, 0x00, 0x4d, 0x02,
+        ]);
+        header
+    };
+
+    let mut expected = Vec::new();
+    for (n, lines) in [2, 3, 2, 3].into_iter().enumerate() {
+        expected.extend(page_header(lines, (n > 0) as u8));
+        expected.extend(std::iter::repeat_n(0x5a, lines as usize));
+        expected.push(if n == 3 { 0x1a } else { 0x0c });
+    }
+    assert_eq!(sent[402..], expected);
+
+    // Nothing to print, nothing sent
+    let mut printer = PTouchPrinter::from_interface(TestInterface::default());
+    printer.print_job(&PrintJob::new().media(media)).unwrap();
+    assert!(printer.interface.sent.is_empty());
+
+    // Cut at end needs chain printing, half cut a half cutter
+    let job = PrintJob::new()
+        .media(media)
+        .page(RasterBuffer::new(2))
+        .half_cut(true);
+    let advanced_mode = |capabilities| {
+        job.commands_for(&capabilities)
+            .into_iter()
+            .find_map(|command| match command {
+                Command::AdvancedMode(mode) => Some(mode),
+                _ => None,
+            })
+            .unwrap()
+    };
+    let ql500 = Model::QL500.capabilities().unwrap();
+    let pt = Model::PTP750W.capabilities().unwrap();
+    assert_eq!(advanced_mode(ql500), AdvancedMode::empty());
+    assert_eq!(
+        advanced_mode(pt),
+        AdvancedMode::NO_CHAIN | AdvancedMode::HALF_CUT
+    );
+}
+
+#[test]
+fn wait_for_job_outcome() {
+    use crate::{emulator::Emulator, media, status::Model};
+
+    let media = media::by_product("DK-22205").unwrap();
+    let job = PrintJob::new()
+        .media(media)
+        .page(RasterBuffer::new(10))
+        .copies(2);
+    let timeout = Duration::from_secs(1);
+
+    let mut printer = PTouchPrinter::from_interface(Emulator::new(Model::QL800, Some(media)));
+    assert_eq!(
+        printer.print_job_and_wait(&job, timeout).unwrap(),
+        JobOutcome::Cut
+    );
+    let job = job.cut(CutMode::None).cut_at_end(false);
+    assert_eq!(
+        printer.print_job_and_wait(&job, timeout).unwrap(),
+        JobOutcome::Printed
+    );
+    assert_eq!(printer.interface.pages().len(), 4);
+
+    printer.interface.set_error(ErrorStatus::COVER_OPEN);
+    match printer.print_job_and_wait(&job, timeout) {
+        Err(e @ PTouchError::Printer(ErrorStatus::COVER_OPEN)) => assert!(e.is_recoverable()),
+        r => panic!("Unexpected {r:?}"),
+    }
+    printer.print_job(&job).unwrap();
+    assert_eq!(
+        printer.wait_for_job(&job, timeout).unwrap(),
+        JobOutcome::Failed(ErrorStatus::COVER_OPEN)
+    );
+
+    // Nothing sent, nothing completes
+    let mut printer = PTouchPrinter::from_interface(Emulator::new(Model::QL800, Some(media)));
+    match printer.wait_for_job(&job, Duration::from_millis(200)) {
+        Err(PTouchError::Timeout) => {}
+        r => panic!("Unexpected {r:?}"),
+    }
+
+    // No cut without a cutter, as the job leaves out auto cut then
+    let job = job.cut(CutMode::EachLabel);
+    let ql = Model::QL800.capabilities().unwrap();
+    let no_cutter = Capabilities {
+        auto_cut: false,
+        ..ql
+    };
+    assert_eq!(job.outcome(Some(&ql)), JobOutcome::Cut);
+    assert_eq!(job.outcome(Some(&no_cutter)), JobOutcome::Printed);
+}
+
+#[test]
+fn validate_job_media() {
+    use crate::{emulator::Emulator, media, status::Model};
+
+    let continuous = media::by_product("DK-22205").unwrap();
+    let die_cut = media::by_product("DK-11201").unwrap();
+
+    let mut printer = PTouchPrinter::from_interface(Emulator::new(Model::QL820NWB, Some(die_cut)));
+    let status = printer.get_status().unwrap();
+
+    let job = PrintJob::new()
+        .media(continuous)
+        .page(RasterBuffer::new(10));
+    let error = job.validate(&status).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "job needs 62mm continuous, printer has 29x90mm die-cut"
+    );
+    assert!(matches!(
+        printer.print_job_and_wait(&job, Duration::from_secs(1)),
+        Err(PTouchError::Job(JobError::WrongMedia { .. }))
+    ));
+    assert!(printer.interface.pages().is_empty());
+
+    let job = PrintJob::new().media(die_cut).page(RasterBuffer::new(992));
+    assert_eq!(
+        job.validate(&status),
+        Err(JobError::PageTooLong {
+            page: 1,
+            lines: 992,
+            max: 991
+        })
+    );
+    assert_eq!(job.high_res(true).validate(&status), Ok(()));
+
+    let job = PrintJob::new()
+        .media(die_cut)
+        .page(RasterBuffer::with_engine(Engine::HandHeld, 10));
+    assert!(matches!(
+        job.validate(&status),
+        Err(JobError::WrongEngine { page: 1, .. })
+    ));
+
+    let job = PrintJob::new()
+        .media(die_cut)
+        .page(TwoColourRasterBuffer::new(10));
+    assert!(matches!(
+        job.validate(&status),
+        Err(JobError::NotTwoColour { page: 1, .. })
+    ));
+
+    let mut printer = PTouchPrinter::from_interface(Emulator::new(Model::QL720NW, Some(die_cut)));
+    let error = job.validate(&printer.get_status().unwrap()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "QL720NW does not support two-colour printing"
+    );
+    let job = PrintJob::new()
+        .media(die_cut)
+        .page(RasterBuffer::new(10))
+        .half_cut(true);
+    assert!(matches!(
+        job.validate(&printer.get_status().unwrap()),
+        Err(JobError::Unsupported {
+            feature: "half cut",
+            ..
+        })
+    ));
+    #[cfg(feature = "net")]
+    {
+        assert!(check_snmp(&printer.get_status().unwrap()).is_ok());
+        let mut usb = PTouchPrinter::from_interface(Emulator::new(Model::QL800, Some(die_cut)));
+        assert!(check_snmp(&usb.get_status().unwrap()).is_err());
+    }
+
+    printer.interface.set_media(None);
+    let status = printer.get_status().unwrap();
+    assert_eq!(PrintJob::new().validate(&status), Err(JobError::NoMedia));
+}
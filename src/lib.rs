@@ -1,19 +1,39 @@
-use std::io;
+use std::{
+    fmt::{self, Display, Formatter},
+    io,
+};
 
+use status::ErrorStatus;
+
+#[cfg(feature = "async")]
+pub mod asynchronous;
 pub mod commands;
+pub mod disasm;
+#[cfg(feature = "net")]
+pub mod discovery;
+pub mod emulator;
 pub mod interface;
+pub mod job;
+pub mod media;
+#[cfg(feature = "net")]
+pub mod monitor;
+pub mod packbits;
 pub mod printer;
 pub mod status;
 pub mod raster;
+pub mod rasterize;
 
 pub mod prelude {
-    pub use super::interface::{PTouchInterface, PTouchTcpInterface};
+    pub use super::interface::{PTouchInterface, PTouchTcpInterface, PTouchWriteInterface};
+    #[cfg(unix)]
+    pub use super::interface::{PTouchSerialInterface, PTouchUsbLpInterface};
     pub use super::printer::{self, PTouchPrinter};
 
     pub use super::commands::{Commands, PrintInfo, VariousMode};
+    pub use super::media::Media;
     pub use super::status::{GetStatus, Status};
     pub use super::Result;
-    pub use super::raster::RasterBuffer;
+    pub use super::raster::{Engine, PrintArea, RasterBuffer, TwoColourRasterBuffer};
 }
 
 pub type Result<T> = std::result::Result<T, PTouchError>;
@@ -21,8 +41,54 @@ pub type Result<T> = std::result::Result<T, PTouchError>;
 #[derive(Debug)]
 pub enum PTouchError {
     IoError(io::Error),
+    /// Status reply of the wrong size or without the print head mark
     InvalidStatusPayload,
-    SNMPError,
+    SNMPError(SnmpError),
+    /// Error condition reported by the printer, e.g. cover open or no media
+    Printer(ErrorStatus),
+    /// The printer did not respond (or finish) in time
+    Timeout,
+    /// The job does not fit the printer or its media, e.g. wrong media or an unsupported feature
+    Job(job::JobError),
+}
+
+impl PTouchError {
+    /// Can the operation be retried (after fixing the printer), or is it pointless to do so
+    pub fn is_recoverable(&self) -> bool {
+        match self {
+            Self::IoError(e) => matches!(
+                e.kind(),
+                io::ErrorKind::TimedOut | io::ErrorKind::WouldBlock | io::ErrorKind::Interrupted
+            ),
+            Self::InvalidStatusPayload | Self::Timeout => true,
+            Self::SNMPError(e) => !matches!(e, SnmpError::NoAddress),
+            Self::Printer(error_status) => error_status.is_recoverable(),
+            Self::Job(_) => false,
+        }
+    }
+}
+
+impl Display for PTouchError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::IoError(e) => write!(f, "I/O error: {e}"),
+            Self::InvalidStatusPayload => write!(f, "invalid status payload"),
+            Self::SNMPError(e) => write!(f, "SNMP error: {e}"),
+            Self::Printer(error_status) => write!(f, "printer error: {error_status}"),
+            Self::Timeout => write!(f, "timed out waiting for the printer"),
+            Self::Job(e) => write!(f, "{e}"),
+        }
+    }
+}
+
+impl std::error::Error for PTouchError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::IoError(e) => Some(e),
+            Self::Job(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 impl From<io::Error> for PTouchError {
@@ -30,3 +96,38 @@ impl From<io::Error> for PTouchError {
         PTouchError::IoError(io_error)
     }
 }
+
+impl From<job::JobError> for PTouchError {
+    fn from(job_error: job::JobError) -> Self {
+        PTouchError::Job(job_error)
+    }
+}
+
+impl From<SnmpError> for PTouchError {
+    fn from(snmp_error: SnmpError) -> Self {
+        PTouchError::SNMPError(snmp_error)
+    }
+}
+
+/// SNMP request failure
+#[derive(Clone, PartialEq, Debug)]
+pub enum SnmpError {
+    /// The interface has no IP address to send the request to
+    NoAddress,
+    /// The request failed, e.g. no response or a malformed one
+    Request(String),
+    /// The response lacks the requested value, or holds a value of another type
+    UnexpectedValue,
+}
+
+impl Display for SnmpError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoAddress => write!(f, "no IP address to query"),
+            Self::Request(e) => write!(f, "request failed ({e})"),
+            Self::UnexpectedValue => write!(f, "unexpected value in response"),
+        }
+    }
+}
+
+impl std::error::Error for SnmpError {}
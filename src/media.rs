@@ -0,0 +1,284 @@
+//! Media catalogue, the printable area of every DK roll and TZe tape width.
+//!
+//! Media is identified by the kind, width and length reported in the `Status` of the printer.
+//! Dot positions are in `RasterBuffer::set_dot` coordinates, e.g. `PrintArea::offset` is the
+//! number of unused dots before the first printable dot.
+
+use std::fmt::{self, Display, Formatter};
+
+use self::MediaForm::*;
+use crate::{
+    commands::PrintInfo,
+    raster::{
+        Engine::{self, DeskLabel, DeskLabelWide},
+        PrintArea,
+    },
+    status::{MediaKind, Status, TextColour},
+};
+
+/// Form factor of the media
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum MediaForm {
+    /// Continuous length tape, cut to the length of the printed data
+    Continuous,
+    /// Pre-cut labels of fixed size
+    DieCut,
+    /// Pre-cut round labels
+    RoundDieCut,
+    /// P-touch TZe (laminated) tape
+    Tape,
+}
+
+/// A label roll or tape cassette
+#[derive(Clone, PartialEq, Debug)]
+pub struct Media {
+    /// Brother product code, e.g. "DK-22205"
+    pub product: Option<&'static str>,
+    pub form: MediaForm,
+    /// Width in mm, as reported in the status
+    pub width: u8,
+    /// Length in mm as reported in the status, 0 for continuous media
+    pub length: u8,
+    /// Print engine (head width) this media is used on
+    pub engine: Engine,
+    /// Printable dots across the print head
+    pub print_area: PrintArea,
+    /// Printable length of a die-cut label in dots (at normal resolution)
+    pub label_length: Option<u32>,
+    /// Pre/post print feed margin in dots, die-cut labels always use 0
+    pub feed_margin: u16,
+    /// Black/red two-colour media
+    pub two_colour: bool,
+}
+
+impl Media {
+    /// Look up the media loaded in the printer, two-colour rolls are told apart by the text colour
+    pub fn from_status(status: &Status) -> Option<&'static Media> {
+        let two_colour = status.text_colour == TextColour::BlackRed;
+        find(
+            status.media_kind,
+            status.media_width,
+            status.media_length,
+            two_colour,
+        )
+    }
+
+    /// Unused dots on the low (`offset`) side of the print head
+    pub fn left_margin(&self) -> u32 {
+        self.print_area.offset
+    }
+
+    /// Unused dots on the high side of the print head
+    pub fn right_margin(&self) -> u32 {
+        self.engine.dots() - self.print_area.offset - self.print_area.dots
+    }
+
+    /// Print information describing this media, raster number to be filled in
+    pub fn print_info(&self) -> PrintInfo {
+        let kind = match self.form {
+            MediaForm::Continuous => MediaKind::ContinuousLengthTape,
+            MediaForm::DieCut | MediaForm::RoundDieCut => MediaKind::DieCutLabels,
+            MediaForm::Tape => MediaKind::LaminatedTape,
+        };
+
+        PrintInfo {
+            kind: Some(kind),
+            width: Some(self.width),
+            length: Some(self.length),
+            ..Default::default()
+        }
+    }
+}
+
+/// Short description, e.g. "62mm continuous" or "29x90mm die-cut"
+impl Display for Media {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self.form {
+            MediaForm::Continuous => write!(f, "{}mm continuous", self.width)?,
+            MediaForm::DieCut => write!(f, "{}x{}mm die-cut", self.width, self.length)?,
+            MediaForm::RoundDieCut => write!(f, "{}mm round die-cut", self.width)?,
+            MediaForm::Tape => write!(f, "{}mm tape", self.width)?,
+        }
+
+        if self.two_colour {
+            write!(f, " (black/red)")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Look up single colour media by the kind, width and length as reported in the status, use
+/// `Media::from_status` to find two-colour media as well
+pub fn lookup(kind: MediaKind, width: u8, length: u8) -> Option<&'static Media> {
+    find(kind, width, length, false)
+}
+
+fn find(kind: MediaKind, width: u8, length: u8, two_colour: bool) -> Option<&'static Media> {
+    MEDIA.iter().find(|m| {
+        let kind_matches = match m.form {
+            MediaForm::Continuous => kind == MediaKind::ContinuousLengthTape,
+            MediaForm::DieCut | MediaForm::RoundDieCut => kind == MediaKind::DieCutLabels,
+            MediaForm::Tape => !matches!(
+                kind,
+                MediaKind::None
+                    | MediaKind::ContinuousLengthTape
+                    | MediaKind::DieCutLabels
+                    | MediaKind::IncompatibleTape(_)
+            ),
+        };
+
+        kind_matches && m.width == width && m.length == length && m.two_colour == two_colour
+    })
+}
+
+/// Look up media by Brother product code, e.g. "DK-11201"
+pub fn by_product(product: &str) -> Option<&'static Media> {
+    MEDIA
+        .iter()
+        .find(|m| m.product.is_some_and(|p| p.eq_ignore_ascii_case(product)))
+}
+
+/// All known media
+pub fn all() -> &'static [Media] {
+    MEDIA
+}
+
+const fn media(
+    product: Option<&'static str>,
+    form: MediaForm,
+    (width, length): (u8, u8),
+    engine: Engine,
+    (offset, dots): (u32, u32),
+    label_length: Option<u32>,
+) -> Media {
+    let feed_margin = match form {
+        MediaForm::Continuous => 35,
+        MediaForm::Tape => 14,
+        MediaForm::DieCut | MediaForm::RoundDieCut => 0,
+    };
+
+    Media {
+        product,
+        form,
+        width,
+        length,
+        engine,
+        print_area: PrintArea { offset, dots },
+        label_length,
+        feed_margin,
+        two_colour: false,
+    }
+}
+
+const fn tape(width: u8) -> Media {
+    let print_area = match Engine::HandHeld.tape_print_area(width) {
+        Some(print_area) => print_area,
+        None => panic!("no print area for tape width"),
+    };
+
+    media(
+        None,
+        MediaForm::Tape,
+        (width, 0),
+        Engine::HandHeld,
+        (print_area.offset, print_area.dots),
+        None,
+    )
+}
+
+#[rustfmt::skip]
+static MEDIA: &[Media] = &[
+    // Continuous length tape
+    media(Some("DK-22214"), Continuous, (12, 0), DeskLabel, (585, 106), None),
+    media(Some("DK-22210"), Continuous, (29, 0), DeskLabel, (408, 306), None),
+    media(Some("DK-22225"), Continuous, (38, 0), DeskLabel, (295, 413), None),
+    media(Some("DK-22223"), Continuous, (50, 0), DeskLabel, (154, 554), None),
+    media(Some("DK-N55224"), Continuous, (54, 0), DeskLabel, (130, 590), None),
+    media(Some("DK-22205"), Continuous, (62, 0), DeskLabel, (12, 696), None),
+    Media {
+        product: Some("DK-22251"),
+        two_colour: true,
+        ..media(None, Continuous, (62, 0), DeskLabel, (12, 696), None)
+    },
+    media(Some("DK-22243"), Continuous, (102, 0), DeskLabelWide, (120, 1164), None),
+    media(Some("DK-22246"), Continuous, (104, 0), DeskLabelWide, (96, 1200), None),
+    // Die-cut labels
+    media(Some("DK-11204"), DieCut, (17, 54), DeskLabel, (555, 165), Some(566)),
+    media(Some("DK-11203"), DieCut, (17, 87), DeskLabel, (555, 165), Some(956)),
+    media(Some("DK-11221"), DieCut, (23, 23), DeskLabel, (476, 202), Some(202)),
+    media(None, DieCut, (29, 42), DeskLabel, (408, 306), Some(425)),
+    media(Some("DK-11201"), DieCut, (29, 90), DeskLabel, (408, 306), Some(991)),
+    media(Some("DK-11208"), DieCut, (38, 90), DeskLabel, (295, 413), Some(991)),
+    media(None, DieCut, (39, 48), DeskLabel, (289, 425), Some(495)),
+    media(None, DieCut, (52, 29), DeskLabel, (142, 578), Some(271)),
+    media(None, DieCut, (54, 29), DeskLabel, (62, 598), Some(271)),
+    media(Some("DK-11234"), DieCut, (60, 86), DeskLabel, (30, 672), Some(954)),
+    media(Some("DK-11209"), DieCut, (62, 29), DeskLabel, (12, 696), Some(271)),
+    media(Some("DK-11202"), DieCut, (62, 100), DeskLabel, (12, 696), Some(1109)),
+    media(Some("DK-11240"), DieCut, (102, 51), DeskLabelWide, (120, 1164), Some(526)),
+    media(Some("DK-11241"), DieCut, (102, 152), DeskLabelWide, (120, 1164), Some(1660)),
+    // Round die-cut labels
+    media(Some("DK-11219"), RoundDieCut, (12, 12), DeskLabel, (513, 94), Some(94)),
+    media(Some("DK-11218"), RoundDieCut, (24, 24), DeskLabel, (442, 236), Some(236)),
+    media(Some("DK-11207"), RoundDieCut, (58, 58), DeskLabel, (51, 618), Some(618)),
+    // TZe tapes (3.5mm tape reports as 4mm)
+    tape(4),
+    tape(6),
+    tape(9),
+    tape(12),
+    tape(18),
+    tape(24),
+];
+
+#[test]
+fn media_lookup() {
+    let m = lookup(MediaKind::ContinuousLengthTape, 62, 0).unwrap();
+    assert_eq!(m.product, Some("DK-22205"));
+    assert_eq!((m.left_margin(), m.right_margin()), (12, 12));
+    assert_eq!(m.feed_margin, 35);
+
+    let m = lookup(MediaKind::DieCutLabels, 29, 90).unwrap();
+    assert_eq!(m.product, Some("DK-11201"));
+    assert_eq!((m.print_area.dots, m.right_margin()), (306, 6));
+    assert_eq!(m.label_length, Some(991));
+    assert_eq!(m.feed_margin, 0);
+
+    let m = lookup(MediaKind::LaminatedTape, 12, 0).unwrap();
+    assert_eq!(
+        (m.engine, m.left_margin(), m.print_area.dots),
+        (Engine::HandHeld, 29, 70)
+    );
+
+    assert!(by_product("dk-22251").unwrap().two_colour);
+    assert_eq!(
+        by_product("DK-22251").unwrap().to_string(),
+        "62mm continuous (black/red)"
+    );
+    assert_eq!(
+        by_product("DK-11201").unwrap().to_string(),
+        "29x90mm die-cut"
+    );
+    assert!(lookup(MediaKind::DieCutLabels, 62, 0).is_none());
+
+    // DK-22205 and DK-22251 only differ in the text colour of the status
+    let mut frame = [0u8; 32];
+    frame[10..12].copy_from_slice(&[62, 0x4a]);
+    let status = Status::from(frame);
+    assert_eq!(
+        Media::from_status(&status).unwrap().product,
+        Some("DK-22205")
+    );
+    frame[25] = 0x81;
+    let status = Status::from(frame);
+    assert_eq!(
+        Media::from_status(&status).unwrap().product,
+        Some("DK-22251")
+    );
+    assert!(lookup(MediaKind::None, 62, 0).is_none());
+
+    for m in all() {
+        let area = m.print_area;
+        assert!(area.offset + area.dots <= m.engine.dots(), "{m:?}");
+    }
+}
@@ -0,0 +1,459 @@
+//! SNMP monitoring of network printers.
+//!
+//! `SnmpMonitor` queries the status and information of a printer over SNMP only, without a
+//! connection to the raw print port, so a dashboard can poll printers while others print.
+//!
+//! ```no_run
+//! use ql_raster::monitor::SnmpMonitor;
+//!
+//! # fn main() -> ql_raster::Result<()> {
+//! let monitor = SnmpMonitor::new("192.168.1.20".parse().unwrap());
+//! println!("{}: {}", monitor.get_name()?, monitor.get_status()?.error_status);
+//! # Ok(())
+//! # }
+//! ```
+
+use std::{
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    thread,
+    time::{Duration, Instant},
+};
+
+use snmp::{SyncSession, Value};
+
+use crate::{
+    interface::SnmpSettings,
+    job::{JobOutcome, PrintJob, POLL_INTERVAL},
+    printer::snmp_oid,
+    status::Status,
+    PTouchError, Result, SnmpError,
+};
+
+/// Decoded SNMP response value
+#[derive(Clone, PartialEq, Debug)]
+pub enum SnmpValue {
+    OctetString(Vec<u8>),
+    Integer(i64),
+    IpAddress(Ipv4Addr),
+    /// Counter32, Counter64 or Gauge32 (Unsigned32)
+    Counter(u64),
+    /// Hundredths of a second
+    Timeticks(u32),
+}
+
+/// Printer state of the host resources MIB (`hrPrinterStatus`)
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PrinterState {
+    Other,
+    Unknown,
+    Idle,
+    Printing,
+    WarmUp,
+}
+
+impl SnmpValue {
+    /// Value of a response varbind, `None` for unsupported types (e.g. `noSuchObject`)
+    pub(crate) fn from_varbind(value: Value) -> Option<Self> {
+        Some(match value {
+            Value::OctetString(data) => Self::OctetString(data.into()),
+            Value::Integer(value) => Self::Integer(value),
+            Value::IpAddress(octets) => Self::IpAddress(octets.into()),
+            Value::Counter32(count) | Value::Unsigned32(count) => Self::Counter(count.into()),
+            Value::Counter64(count) => Self::Counter(count),
+            Value::Timeticks(ticks) => Self::Timeticks(ticks),
+            _ => return None,
+        })
+    }
+
+    // Decoding of the typed getters, shared by `SnmpMonitor` and the async monitor
+
+    pub(crate) fn into_octets(self) -> Result<Vec<u8>> {
+        match self {
+            Self::OctetString(data) => Ok(data),
+            _ => Err(SnmpError::UnexpectedValue.into()),
+        }
+    }
+
+    pub(crate) fn into_string(self) -> Result<String> {
+        Ok(String::from_utf8_lossy(&self.into_octets()?).into())
+    }
+
+    pub(crate) fn into_status(self) -> Result<Status> {
+        Status::try_from(self.into_octets()?.as_slice())
+    }
+
+    /// An IP address, also as 4 octets or text
+    pub(crate) fn into_ipv4(self) -> Result<Ipv4Addr> {
+        match self {
+            Self::IpAddress(addr) => Ok(addr),
+            Self::OctetString(data) => match <[u8; 4]>::try_from(data.as_slice()) {
+                Ok(octets) => Ok(octets.into()),
+                Err(_) => String::from_utf8_lossy(&data)
+                    .trim()
+                    .parse()
+                    .map_err(|_| SnmpError::UnexpectedValue.into()),
+            },
+            _ => Err(SnmpError::UnexpectedValue.into()),
+        }
+    }
+
+    pub(crate) fn into_mac(self) -> Result<[u8; 6]> {
+        let mac = self.into_octets()?;
+        match <[u8; 6]>::try_from(mac.as_slice()) {
+            Ok(mac) => Ok(mac),
+            // Some firmware reports the MAC address as text, e.g. "00:80:92:7A:FB:CE"
+            Err(_) => parse_mac(&String::from_utf8_lossy(&mac))
+                .ok_or_else(|| SnmpError::UnexpectedValue.into()),
+        }
+    }
+
+    /// A counter, also as a non-negative integer
+    pub(crate) fn into_counter(self) -> Result<u64> {
+        match self {
+            Self::Counter(count) => Ok(count),
+            Self::Integer(count) => {
+                u64::try_from(count).map_err(|_| SnmpError::UnexpectedValue.into())
+            }
+            _ => Err(SnmpError::UnexpectedValue.into()),
+        }
+    }
+
+    pub(crate) fn into_duration(self) -> Result<Duration> {
+        match self {
+            Self::Timeticks(ticks) => Ok(Duration::from_millis(ticks as u64 * 10)),
+            _ => Err(SnmpError::UnexpectedValue.into()),
+        }
+    }
+
+    pub(crate) fn into_printer_state(self) -> Result<PrinterState> {
+        match self {
+            Self::Integer(value) => Ok(value.into()),
+            _ => Err(SnmpError::UnexpectedValue.into()),
+        }
+    }
+}
+
+impl From<i64> for PrinterState {
+    fn from(value: i64) -> Self {
+        match value {
+            1 => Self::Other,
+            3 => Self::Idle,
+            4 => Self::Printing,
+            5 => Self::WarmUp,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// SNMP client of a single printer
+#[derive(Clone, PartialEq, Debug)]
+pub struct SnmpMonitor {
+    ip_addr: IpAddr,
+    settings: SnmpSettings,
+}
+
+impl SnmpMonitor {
+    /// Monitor the printer at `ip_addr`, with the default `SnmpSettings`
+    pub fn new(ip_addr: IpAddr) -> Self {
+        Self::with_settings(ip_addr, SnmpSettings::default())
+    }
+
+    pub fn with_settings(ip_addr: IpAddr, settings: SnmpSettings) -> Self {
+        Self { ip_addr, settings }
+    }
+
+    pub fn ip_addr(&self) -> IpAddr {
+        self.ip_addr
+    }
+
+    pub fn settings(&self) -> &SnmpSettings {
+        &self.settings
+    }
+
+    pub fn set_settings(&mut self, settings: SnmpSettings) {
+        self.settings = settings;
+    }
+
+    pub fn get_status(&self) -> Result<Status> {
+        self.get_value(snmp_oid::STATUS)?.into_status()
+    }
+
+    pub fn get_name(&self) -> Result<String> {
+        self.get_value(snmp_oid::NAME)?.into_string()
+    }
+
+    pub fn get_model(&self) -> Result<String> {
+        self.get_value(snmp_oid::MODEL)?.into_string()
+    }
+
+    pub fn get_serial(&self) -> Result<String> {
+        self.get_value(snmp_oid::SERIAL)?.into_string()
+    }
+
+    pub fn get_ip_addr(&self) -> Result<Ipv4Addr> {
+        self.get_value(snmp_oid::IP_ADDR)?.into_ipv4()
+    }
+
+    pub fn get_subnet(&self) -> Result<Ipv4Addr> {
+        self.get_value(snmp_oid::SUBNET)?.into_ipv4()
+    }
+
+    pub fn get_mac(&self) -> Result<[u8; 6]> {
+        self.get_value(snmp_oid::MAC)?.into_mac()
+    }
+
+    /// Time since the network interface was started
+    pub fn get_uptime(&self) -> Result<Duration> {
+        self.get_value(snmp_oid::UPTIME)?.into_duration()
+    }
+
+    /// Pages printed over the lifetime of the printer
+    pub fn get_page_count(&self) -> Result<u64> {
+        self.get_value(snmp_oid::LIFE_COUNT)?.into_counter()
+    }
+
+    /// Pages printed since the printer was turned on
+    pub fn get_power_on_page_count(&self) -> Result<u64> {
+        self.get_value(snmp_oid::POWER_ON_COUNT)?.into_counter()
+    }
+
+    /// Printer state as reported by the standard MIB, see `get_status` for the details
+    pub fn get_printer_state(&self) -> Result<PrinterState> {
+        self.get_value(snmp_oid::PRINTER_STATUS)?
+            .into_printer_state()
+    }
+
+    /// Query a single OID, e.g. one of a vendor MIB
+    pub fn get_value(&self, oid: &[u32]) -> Result<SnmpValue> {
+        let settings = &self.settings;
+        let addr = SocketAddr::new(self.ip_addr, settings.port);
+        let mut snmp_session = SyncSession::new(
+            addr,
+            settings.community.as_bytes(),
+            Some(settings.timeout),
+            0,
+        )?;
+
+        let mut attempt = 0;
+        let mut response = loop {
+            match snmp_session.get(oid) {
+                Ok(response) => break response,
+                Err(_) if attempt < settings.retries => attempt += 1,
+                Err(e) => return Err(SnmpError::Request(format!("{e:?}")).into()),
+            }
+        };
+
+        response
+            .varbinds
+            .next()
+            .and_then(|(_oid, value)| SnmpValue::from_varbind(value))
+            .ok_or_else(|| SnmpError::UnexpectedValue.into())
+    }
+
+    /// Poll the status and page counter until every page of `job` is printed. `start_count` is
+    /// the page counter (`get_page_count`) as read before the job was sent, so jobs finishing
+    /// between two polls are not missed. Fails with `PTouchError::Timeout` when the job is not
+    /// done within `timeout`
+    pub fn wait_for_job(
+        &self,
+        job: &PrintJob,
+        start_count: u64,
+        timeout: Duration,
+    ) -> Result<JobOutcome> {
+        let deadline = Instant::now() + timeout;
+        let tracker = JobTracker::new(start_count);
+
+        loop {
+            let status = self.get_status()?;
+            let page_count = self.get_page_count()?;
+            if let Some(outcome) = tracker.update(job, &status, page_count) {
+                return Ok(outcome);
+            }
+
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(PTouchError::Timeout);
+            }
+            thread::sleep(POLL_INTERVAL.min(deadline - now));
+        }
+    }
+}
+
+/// Follows the progress of a job in the polled status and page counter, see
+/// `SnmpMonitor::wait_for_job`
+pub(crate) struct JobTracker {
+    start_count: u64,
+}
+
+impl JobTracker {
+    pub(crate) fn new(start_count: u64) -> Self {
+        Self { start_count }
+    }
+
+    /// Outcome of the job, once it is done
+    pub(crate) fn update(
+        &self,
+        job: &PrintJob,
+        status: &Status,
+        page_count: u64,
+    ) -> Option<JobOutcome> {
+        if !status.error_status.is_empty() {
+            return Some(JobOutcome::Failed(status.error_status));
+        }
+
+        let printed = page_count.saturating_sub(self.start_count);
+        (printed >= job.page_count() as u64)
+            .then(|| job.outcome(status.model.capabilities().as_ref()))
+    }
+}
+
+/// MAC address in the usual text form, separated by colons or dashes
+fn parse_mac(text: &str) -> Option<[u8; 6]> {
+    let octets: Vec<u8> = text
+        .trim()
+        .split([':', '-'])
+        .map(|octet| u8::from_str_radix(octet, 16).ok())
+        .collect::<Option<_>>()?;
+
+    octets.try_into().ok()
+}
+
+#[test]
+fn snmp_monitor() {
+    use crate::{
+        emulator::{Emulator, EmulatorServer},
+        media,
+        prelude::*,
+        status::Model,
+    };
+
+    let media = media::by_product("DK-22205").unwrap();
+    let server =
+        EmulatorServer::bind("127.0.0.1:0", Emulator::new(Model::QL810W, Some(media))).unwrap();
+    let agent = server.serve_snmp("127.0.0.1:0").unwrap();
+    let port = agent.local_addr().port();
+    let settings = SnmpSettings {
+        port,
+        ..SnmpSettings::default()
+    };
+
+    // No connection to the print port needed
+    let monitor = SnmpMonitor::with_settings(Ipv4Addr::LOCALHOST.into(), settings.clone());
+    assert_eq!(monitor.get_model().unwrap(), "Brother QL-810W");
+    assert_eq!(monitor.get_status().unwrap().model, Model::QL810W);
+    assert_eq!(monitor.get_ip_addr().unwrap(), Ipv4Addr::LOCALHOST);
+    assert_eq!(
+        monitor.get_subnet().unwrap(),
+        Ipv4Addr::new(255, 255, 255, 0)
+    );
+    let [port_hi, port_lo] = port.to_be_bytes();
+    assert_eq!(
+        monitor.get_mac().unwrap(),
+        [0x00, 0x80, 0x92, 0x00, port_hi, port_lo]
+    );
+    assert!(monitor.get_uptime().unwrap() < Duration::from_secs(60));
+    assert_eq!(monitor.get_printer_state().unwrap(), PrinterState::Idle);
+    assert_eq!(monitor.get_page_count().unwrap(), 0);
+
+    // The printer uses the SNMP settings of its interface
+    let mut printer = printer::from_addr(server.local_addr()).unwrap();
+    printer.interface.set_snmp_settings(settings);
+    assert_eq!(printer.snmp_monitor().unwrap(), monitor);
+    printer
+        .print_job(&PrintJob::new().media(media).page(RasterBuffer::new(10)))
+        .unwrap();
+    printer.read_status().unwrap();
+    assert_eq!(printer.get_snmp_page_count().unwrap(), 1);
+    assert_eq!(
+        monitor.get_value(snmp_oid::POWER_ON_COUNT).unwrap(),
+        SnmpValue::Counter(1)
+    );
+
+    assert_eq!(
+        parse_mac("00:80:92:7a:fb:ce"),
+        Some([0, 0x80, 0x92, 0x7a, 0xfb, 0xce])
+    );
+    assert_eq!(parse_mac("00-80-92"), None);
+
+    // Nothing listening, the request fails after the retries
+    let monitor = SnmpMonitor::with_settings(
+        Ipv4Addr::LOCALHOST.into(),
+        SnmpSettings {
+            port: server.local_addr().port(),
+            timeout: Duration::from_millis(50),
+            ..SnmpSettings::default()
+        },
+    );
+    match monitor.get_name() {
+        Err(PTouchError::SNMPError(SnmpError::Request(_))) => {}
+        r => panic!("Unexpected {r:?}"),
+    }
+}
+
+#[test]
+fn wait_for_job_snmp() {
+    use crate::{
+        emulator::{Emulator, EmulatorServer},
+        media,
+        prelude::*,
+        status::{ErrorStatus, Model, Phase},
+    };
+
+    let media = media::by_product("DK-22205").unwrap();
+    let mut emulator = Emulator::new(Model::QL820NWB, Some(media));
+    emulator.set_print_time(Duration::from_millis(300));
+    let server = EmulatorServer::bind("127.0.0.1:0", emulator).unwrap();
+    let agent = server.serve_snmp("127.0.0.1:0").unwrap();
+
+    let mut printer = printer::from_addr(server.local_addr()).unwrap();
+    printer.interface.set_snmp_settings(SnmpSettings {
+        port: agent.local_addr().port(),
+        ..SnmpSettings::default()
+    });
+    let monitor = printer.snmp_monitor().unwrap();
+    let job = PrintJob::new()
+        .media(media)
+        .page(RasterBuffer::new(10))
+        .copies(2);
+    let timeout = Duration::from_secs(3);
+
+    // The printer goes through the printing phase and counts the pages once they are done
+    let start_count = monitor.get_page_count().unwrap();
+    let started = Instant::now();
+    printer.print_job(&job).unwrap();
+    while monitor.get_status().unwrap().phase != Phase::Printing {
+        assert!(started.elapsed() < timeout);
+    }
+    assert_eq!(monitor.get_printer_state().unwrap(), PrinterState::Printing);
+    assert_eq!(
+        monitor.wait_for_job(&job, start_count, timeout).unwrap(),
+        JobOutcome::Cut
+    );
+    assert!(started.elapsed() >= Duration::from_millis(600));
+    assert_eq!(monitor.get_status().unwrap().phase, Phase::Receiving);
+    assert_eq!(monitor.get_page_count().unwrap(), start_count + 2);
+
+    // A job done before the first poll is not missed
+    server.emulator().set_print_time(Duration::ZERO);
+    assert_eq!(
+        printer.print_job_and_wait_snmp(&job, timeout).unwrap(),
+        JobOutcome::Cut
+    );
+    let start_count = monitor.get_page_count().unwrap();
+    printer.print_job(&job).unwrap();
+    thread::sleep(Duration::from_millis(200));
+    assert_eq!(
+        monitor
+            .wait_for_job(&job, start_count, Duration::ZERO)
+            .unwrap(),
+        JobOutcome::Cut
+    );
+
+    server.emulator().set_error(ErrorStatus::COVER_OPEN);
+    assert_eq!(
+        monitor
+            .wait_for_job(&job, start_count + 2, timeout)
+            .unwrap(),
+        JobOutcome::Failed(ErrorStatus::COVER_OPEN)
+    );
+}
@@ -0,0 +1,91 @@
+//! PackBits run-length coding, the 'TIFF' compression used for raster lines.
+//!
+//! A header byte `n` is followed by either `n + 1` literal bytes (`0..=127`), or a single
+//! byte that is repeated `1 - n` times (`-127..=-1`). `-128` is a no-op.
+
+/// Longest literal or repeat run a single header can describe
+const MAX_RUN: usize = 128;
+
+/// Compress `data` using PackBits
+pub fn encode(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + data.len().div_ceil(MAX_RUN));
+    let mut i = 0;
+
+    while i < data.len() {
+        let run = data[i..]
+            .iter()
+            .take(MAX_RUN)
+            .take_while(|&&b| b == data[i])
+            .count();
+
+        if run >= 2 {
+            out.push((1 - run as i16) as u8);
+            out.push(data[i]);
+            i += run;
+        } else {
+            // Literal run, up to the next run of three or more equal bytes
+            let start = i;
+            while i < data.len() && i - start < MAX_RUN {
+                if i + 2 < data.len() && data[i] == data[i + 1] && data[i] == data[i + 2] {
+                    break;
+                }
+                i += 1;
+            }
+
+            out.push((i - start - 1) as u8);
+            out.extend_from_slice(&data[start..i]);
+        }
+    }
+
+    out
+}
+
+/// Decompress PackBits `data`, returns `None` when the input is truncated
+pub fn decode(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(data.len() * 2);
+    let mut i = 0;
+
+    while i < data.len() {
+        let n = data[i] as i8;
+        i += 1;
+
+        match n {
+            -128 => {}
+            0..=127 => {
+                let len = n as usize + 1;
+                out.extend_from_slice(data.get(i..i + len)?);
+                i += len;
+            }
+            _ => {
+                let b = *data.get(i)?;
+                out.resize(out.len() + (1 - n as isize) as usize, b);
+                i += 1;
+            }
+        }
+    }
+
+    Some(out)
+}
+
+#[test]
+fn packbits_roundtrip() {
+    // Example from Apple TN1023
+    let raw = [
+        0xaa, 0xaa, 0xaa, 0x80, 0x00, 0x2a, 0xaa, 0xaa, 0xaa, 0xaa, 0x80, 0x00, 0x2a, 0x22, 0xaa,
+        0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa, 0xaa,
+    ];
+    let packed = [
+        0xfe, 0xaa, 0x02, 0x80, 0x00, 0x2a, 0xfd, 0xaa, 0x03, 0x80, 0x00, 0x2a, 0x22, 0xf7, 0xaa,
+    ];
+    assert_eq!(encode(&raw), packed);
+    assert_eq!(decode(&packed).unwrap(), raw);
+
+    // Long runs and literals are split at 128 bytes
+    let line: Vec<u8> = (0..300u32).map(|i| (i * 7 % 251) as u8).collect();
+    assert_eq!(decode(&encode(&line)).unwrap(), line);
+    assert_eq!(encode(&[0u8; 90]), [0xa7, 0x00]);
+    assert_eq!(decode(&encode(&[0xffu8; 200])).unwrap(), [0xffu8; 200]);
+
+    assert_eq!(decode(&[0x02, 0x01]), None);
+    assert_eq!(decode(&[0xfe]), None);
+}
@@ -1,14 +1,36 @@
-use crate::{prelude::*, PTouchError};
+use crate::{
+    commands::{status_frame, supported_compression, Command, CompressionMode},
+    interface::PTouchWriteInterface,
+    prelude::*,
+    status::{Capabilities, Model},
+};
 use std::{
-    net::{SocketAddr, ToSocketAddrs},
+    fs::File,
+    io::{BufWriter, Read, Write},
+    net::ToSocketAddrs,
+    path::Path,
     time::Duration,
 };
 
+#[cfg(feature = "net")]
+use crate::{
+    monitor::{PrinterState, SnmpMonitor, SnmpValue},
+    SnmpError,
+};
+#[cfg(feature = "net")]
+use std::net::Ipv4Addr;
+
+#[cfg(unix)]
+use crate::interface::{PTouchSerialInterface, PTouchUsbLpInterface, SerialSettings};
+
 const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1500);
 
+/// Default send buffer limit for buffered IO, enough for a typical label in a single write
+pub const DEFAULT_BUFFER_LIMIT: usize = 256 * 1024;
+
 /// SNMP OIDs for getting information from the printer over the network interface e.g. `Status`
 #[allow(dead_code)]
-mod snmp_oid {
+pub(crate) mod snmp_oid {
     pub const STATUS: &[u32] = &[1, 3, 6, 1, 4, 1, 2435, 3, 3, 9, 1, 6, 1, 0];
     pub const NAME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 6, 0];
     pub const MODEL: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 3, 2, 1, 3, 1];
@@ -16,97 +38,411 @@ mod snmp_oid {
     pub const IP_ADDR: &[u32] = &[1, 3, 6, 1, 4, 1, 1240, 2, 3, 4, 5, 2, 3, 0];
     pub const SUBNET: &[u32] = &[1, 3, 6, 1, 4, 1, 1240, 2, 3, 4, 5, 2, 4, 0];
     pub const MAC: &[u32] = &[1, 3, 6, 1, 4, 1, 1240, 2, 3, 4, 5, 2, 12, 0];
+    pub const UPTIME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3, 0];
+    /// Host resources MIB `hrPrinterStatus`
+    pub const PRINTER_STATUS: &[u32] = &[1, 3, 6, 1, 2, 1, 25, 3, 5, 1, 1, 1];
+    /// Printer MIB `prtMarkerLifeCount`, pages printed over the lifetime of the printer
+    pub const LIFE_COUNT: &[u32] = &[1, 3, 6, 1, 2, 1, 43, 10, 2, 1, 4, 1, 1];
+    /// Printer MIB `prtMarkerPowerOnCount`, pages printed since power on
+    pub const POWER_ON_COUNT: &[u32] = &[1, 3, 6, 1, 2, 1, 43, 10, 2, 1, 5, 1, 1];
 }
 
-// pub enum EngineHandHeld {} // 128px wide @ 180dpi (18mm)
-// pub enum EngineDeskLabel {} // 720px wide @ 300dpi (61mm)
-// pub enum EngineDeskLabelWide {} // 1296px wide @ 300dpi (110mm)
-
-// const DOTS_PER_LINE: usize = 720;
-// const BYTES_PER_LINE: usize = DOTS_PER_LINE / 8;
-
 pub struct PTouchPrinter<D> {
     pub interface: D,
-    send_buffer: Option<Vec<u8>>, // Probably use a type (of PTouchPrinter) to diff between buffered and direct io
+    pub(crate) protocol: Protocol,
+}
+
+/// Sans-IO state of a printer connection, shared by `PTouchPrinter` and the async printer.
+/// Turns commands into the bytes due for the interface and status bytes into a `Status`, the
+/// printers only move the bytes
+#[derive(Debug)]
+pub(crate) struct Protocol {
+    /// Commands waiting for the next `flush`, `None` for direct (unbuffered) IO
+    send_buffer: Option<Vec<u8>>,
+    /// Size at which the send buffer is written out, even without a `flush`
+    buffer_limit: usize,
+    /// Compression mode as last set on the device, raster lines are encoded accordingly
+    compression: CompressionMode,
+    /// Print engine, determines the raster line framing
+    engine: Engine,
+    /// Capabilities of the model, once known
+    capabilities: Option<Capabilities>,
+    /// Status frame being read and the number of bytes received so far, kept across reads so
+    /// an interrupted read doesn't lose part of a frame
+    status: [u8; 32],
+    status_len: usize,
+}
+
+impl Protocol {
+    /// Direct IO with `None`, else buffered IO with the given limit
+    pub(crate) fn new(buffer_limit: Option<usize>) -> Self {
+        Protocol {
+            send_buffer: buffer_limit
+                .map(|limit| Vec::with_capacity(limit.min(DEFAULT_BUFFER_LIMIT))),
+            buffer_limit: buffer_limit.unwrap_or(DEFAULT_BUFFER_LIMIT),
+            compression: CompressionMode::None,
+            engine: Engine::default(),
+            capabilities: None,
+            status: [0; 32],
+            status_len: 0,
+        }
+    }
+
+    pub(crate) fn is_buffered(&self) -> bool {
+        self.send_buffer.is_some()
+    }
+
+    /// Switch between buffered and direct IO, the send buffer must be flushed first
+    pub(crate) fn set_buffered(&mut self, limit: Option<usize>) {
+        match limit {
+            Some(limit) => {
+                self.send_buffer.get_or_insert_with(Vec::new);
+                self.buffer_limit = limit;
+            }
+            None => self.send_buffer = None,
+        }
+    }
+
+    pub(crate) fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    pub(crate) fn set_engine(&mut self, engine: Engine) {
+        self.engine = engine;
+    }
+
+    pub(crate) fn set_model(&mut self, model: Model) {
+        if let Some(capabilities) = model.capabilities() {
+            self.set_capabilities(capabilities);
+        }
+    }
+
+    pub(crate) fn set_capabilities(&mut self, capabilities: Capabilities) {
+        self.engine = capabilities.engine;
+        self.capabilities = Some(capabilities);
+    }
+
+    pub(crate) fn capabilities(&self) -> Option<Capabilities> {
+        self.capabilities
+    }
+
+    /// Encode a single command, tracking the compression mode of the printer
+    pub(crate) fn encode(&mut self, command: &Command) -> Vec<u8> {
+        self.track(command);
+        command.encode()
+    }
+
+    /// Follow the compression mode of the printer through a command sent to it
+    pub(crate) fn track(&mut self, command: &Command) {
+        match command {
+            Command::Init => self.compression = CompressionMode::None,
+            Command::CompressionMode(mode) => self.compression = *mode,
+            _ => {}
+        }
+    }
+
+    /// Compression mode command for `mode`, falling back to what the printer supports
+    pub(crate) fn compression_mode(&self, mode: CompressionMode) -> Command {
+        let mode = supported_compression(mode, self.engine, self.capabilities.as_ref());
+        Command::CompressionMode(mode)
+    }
+
+    /// Raster line command for `data`, tape printers only take TIFF compressed lines
+    pub(crate) fn raster_line(&mut self, data: &[u8]) -> Command {
+        if self.engine.is_tape() {
+            self.compression = CompressionMode::Tiff;
+        }
+
+        Command::raster_line(data, self.engine, self.compression)
+    }
+
+    /// Black and red plane commands of a two-colour raster line
+    pub(crate) fn two_colour_raster_line(&self, black: &[u8], red: &[u8]) -> [Command; 2] {
+        Command::two_colour_raster_line(black, red, self.compression)
+    }
+
+    /// Commands of `job` for this printer, see `PrintJob::commands_for`
+    #[cfg(feature = "async")]
+    pub(crate) fn job_commands(&self, job: &crate::job::PrintJob) -> Vec<Command> {
+        match self.capabilities {
+            Some(capabilities) => job.commands_for(&capabilities),
+            None => job.commands(self.engine),
+        }
+    }
+
+    /// Add `data` to the send buffer, returns the bytes to write to the interface right away:
+    /// all of it for direct IO, or a full buffer (followed by `data` when that doesn't fit
+    /// the buffer on its own)
+    pub(crate) fn queue(&mut self, data: &[u8]) -> Option<Vec<u8>> {
+        let Some(buffer) = self.send_buffer.as_mut() else {
+            return Some(data.to_vec());
+        };
+
+        if buffer.len() + data.len() <= self.buffer_limit {
+            buffer.extend_from_slice(data);
+            return None;
+        }
+
+        let mut due = std::mem::take(buffer);
+        match data.len() > self.buffer_limit {
+            true => due.extend_from_slice(data),
+            false => buffer.extend_from_slice(data),
+        }
+
+        Some(due).filter(|due| !due.is_empty())
+    }
+
+    /// Everything waiting in the send buffer, for a flush
+    pub(crate) fn take_buffered(&mut self) -> Option<Vec<u8>> {
+        self.send_buffer
+            .as_mut()
+            .filter(|buffer| !buffer.is_empty())
+            .map(std::mem::take)
+    }
+
+    /// The part of the status frame still to be read
+    pub(crate) fn status_buf(&mut self) -> &mut [u8] {
+        &mut self.status[self.status_len..]
+    }
+
+    /// Account for `n` bytes read into `status_buf`, returns the status once the frame is
+    /// complete. The engine and capabilities follow the model in the status
+    pub(crate) fn status_read(&mut self, n: usize) -> Option<Result<Status>> {
+        self.status_len += n;
+        if self.status_len < self.status.len() {
+            return None;
+        }
+
+        self.status_len = 0;
+        let status = status_frame(self.status);
+        if let Ok(status) = &status {
+            self.set_model(status.model);
+        }
+
+        Some(status)
+    }
 }
 
 impl PTouchPrinter<PTouchTcpInterface> {
     pub fn new<A: ToSocketAddrs>(addr: A) -> Result<Self> {
         from_addr(addr)
     }
+}
+
+#[cfg(feature = "net")]
+impl PTouchPrinter<PTouchTcpInterface> {
+    /// SNMP client for the printer, using the `SnmpSettings` of the interface
+    pub fn snmp_monitor(&self) -> Result<SnmpMonitor> {
+        let ip_addr = self.interface.ip_addr.ok_or(SnmpError::NoAddress)?;
+        Ok(SnmpMonitor::with_settings(
+            ip_addr,
+            self.interface.snmp_settings().clone(),
+        ))
+    }
 
     pub fn get_snmp_status(&self) -> Result<Status> {
-        Status::try_from(self.get_snmp(snmp_oid::STATUS)?.as_slice())
+        self.snmp_monitor()?.get_status()
     }
 
     pub fn get_snmp_name(&self) -> Result<String> {
-        self.get_snmp_string(snmp_oid::NAME)
+        self.snmp_monitor()?.get_name()
     }
 
     pub fn get_snmp_model(&self) -> Result<String> {
-        self.get_snmp_string(snmp_oid::MODEL)
+        self.snmp_monitor()?.get_model()
     }
 
     pub fn get_snmp_serial(&self) -> Result<String> {
-        self.get_snmp_string(snmp_oid::SERIAL)
+        self.snmp_monitor()?.get_serial()
     }
 
-    fn get_snmp_string(&self, oid: &[u32]) -> Result<String> {
-        let r = self.get_snmp(oid)?;
-        Ok(String::from_utf8_lossy(r.as_slice()).into())
+    pub fn get_snmp_ip_addr(&self) -> Result<Ipv4Addr> {
+        self.snmp_monitor()?.get_ip_addr()
     }
 
-    fn get_snmp(&self, oid: &[u32]) -> Result<Vec<u8>> {
-        use snmp::{SyncSession, Value};
+    pub fn get_snmp_subnet(&self) -> Result<Ipv4Addr> {
+        self.snmp_monitor()?.get_subnet()
+    }
 
-        if let Some(ip_addr) = self.interface.ip_addr {
-            let addr = SocketAddr::new(ip_addr, 161);
-            let timeout = Duration::from_millis(500);
-            let mut snmp_session = SyncSession::new(addr, b"public", Some(timeout), 0)?;
+    pub fn get_snmp_mac(&self) -> Result<[u8; 6]> {
+        self.snmp_monitor()?.get_mac()
+    }
 
-            let mut response = snmp_session.get(oid).map_err(|_| PTouchError::SNMPError)?;
-            if let Some((_oid, Value::OctetString(response_data))) = response.varbinds.next() {
-                return Ok(response_data.into());
-            }
-        }
+    /// Time since the network interface was started
+    pub fn get_snmp_uptime(&self) -> Result<Duration> {
+        self.snmp_monitor()?.get_uptime()
+    }
+
+    /// Pages printed over the lifetime of the printer
+    pub fn get_snmp_page_count(&self) -> Result<u64> {
+        self.snmp_monitor()?.get_page_count()
+    }
+
+    /// Pages printed since the printer was turned on
+    pub fn get_snmp_power_on_page_count(&self) -> Result<u64> {
+        self.snmp_monitor()?.get_power_on_page_count()
+    }
+
+    /// Printer state as reported by the standard MIB, see `get_snmp_status` for the details
+    pub fn get_snmp_printer_state(&self) -> Result<PrinterState> {
+        self.snmp_monitor()?.get_printer_state()
+    }
+
+    /// Query a single OID, see `SnmpMonitor::get_value`
+    pub fn get_snmp_value(&self, oid: &[u32]) -> Result<SnmpValue> {
+        self.snmp_monitor()?.get_value(oid)
+    }
+}
 
-        Err(PTouchError::SNMPError)
+#[cfg(unix)]
+impl PTouchPrinter<PTouchUsbLpInterface> {
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self> {
+        from_usb_lp(path)
     }
 }
 
 pub fn from_addr<A: ToSocketAddrs>(addr: A) -> Result<PTouchPrinter<PTouchTcpInterface>> {
-    Ok(PTouchPrinter {
-        interface: PTouchTcpInterface::new(addr, Some(DEFAULT_TIMEOUT))?,
-
-        // // buffered IO
-        // send_buffer: Some(Vec::with_capacity(2048)),
-        // unbuffered, immediate IO
-        send_buffer: None,
-    })
+    Ok(PTouchPrinter::from_interface(PTouchTcpInterface::new(
+        addr,
+        Some(DEFAULT_TIMEOUT),
+    )?))
+}
+
+/// Like `from_addr`, with buffered IO: a job goes out in a single write on `flush`, instead of
+/// a TCP segment per command
+pub fn from_addr_buffered<A: ToSocketAddrs>(addr: A) -> Result<PTouchPrinter<PTouchTcpInterface>> {
+    Ok(PTouchPrinter::buffered(
+        PTouchTcpInterface::new(addr, Some(DEFAULT_TIMEOUT))?,
+        DEFAULT_BUFFER_LIMIT,
+    ))
+}
+
+/// Capture the command stream to `writer` instead of sending it to a printer
+pub fn from_writer<W: Write>(writer: W) -> PTouchPrinter<PTouchWriteInterface<W>> {
+    PTouchPrinter::from_interface(PTouchWriteInterface::new(writer))
+}
+
+/// Capture the command stream to a (`.prn`) file, to be replayed to a printer later
+pub fn to_file<P: AsRef<Path>>(
+    path: P,
+) -> Result<PTouchPrinter<PTouchWriteInterface<BufWriter<File>>>> {
+    Ok(from_writer(BufWriter::new(File::create(path)?)))
+}
+
+/// Open a USB printer class device node, e.g. `/dev/usb/lp0`
+#[cfg(unix)]
+pub fn from_usb_lp<P: AsRef<Path>>(path: P) -> Result<PTouchPrinter<PTouchUsbLpInterface>> {
+    Ok(PTouchPrinter::from_interface(PTouchUsbLpInterface::new(
+        path,
+        Some(DEFAULT_TIMEOUT),
+    )?))
+}
+
+/// Open a serial tty, e.g. a Bluetooth SPP `/dev/rfcomm0` or USB-serial device
+#[cfg(unix)]
+pub fn from_serial<P: AsRef<Path>>(
+    path: P,
+    settings: &SerialSettings,
+) -> Result<PTouchPrinter<PTouchSerialInterface>> {
+    Ok(PTouchPrinter::from_interface(PTouchSerialInterface::new(
+        path, settings,
+    )?))
 }
 
 impl<D: PTouchInterface> PTouchPrinter<D> {
+    /// Direct IO, every command is written to the interface right away
+    pub fn from_interface(interface: D) -> Self {
+        PTouchPrinter {
+            interface,
+            protocol: Protocol::new(None),
+        }
+    }
+
+    /// Buffered IO, commands are collected until `flush` (or until `limit` bytes are waiting)
+    /// and then written to the interface at once
+    pub fn buffered(interface: D, limit: usize) -> Self {
+        PTouchPrinter {
+            interface,
+            protocol: Protocol::new(Some(limit)),
+        }
+    }
+
+    pub fn is_buffered(&self) -> bool {
+        self.protocol.is_buffered()
+    }
+
+    /// Switch to buffered IO with the given limit, or to direct IO with `None`.
+    /// Anything still buffered is sent first
+    pub fn set_buffered(&mut self, limit: Option<usize>) -> Result<()> {
+        self.flush()?;
+        self.protocol.set_buffered(limit);
+
+        Ok(())
+    }
+
+    pub fn engine(&self) -> Engine {
+        self.protocol.engine()
+    }
+
+    /// Select the print engine, defaults to the (720px wide) desk label engine.
+    /// Note the engine is also set from the model in every status read over the data channel
+    pub fn set_engine(&mut self, engine: Engine) {
+        self.protocol.set_engine(engine);
+    }
+
+    /// Select the print engine and capabilities of `model`, unknown models leave both unchanged
+    pub fn set_model(&mut self, model: Model) {
+        self.protocol.set_model(model);
+    }
+
+    /// Capabilities of the printer, known after a status read over the data channel or `set_model`
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        self.protocol.capabilities()
+    }
+
         // pub fn get_status(&mut self) -> Result<Status> {
         //     Ok(Status)
         // }
 
-    // Todo: send `Command` type
+    /// Send a single command, tracking the compression mode of the printer
+    pub fn send(&mut self, command: &Command) -> Result<()> {
+        let data = self.protocol.encode(command);
+        self.write(data)
+    }
+
+    /// Send raw bytes, or add them to the send buffer. A full buffer is written out first
     pub fn write(&mut self, data: impl AsRef<[u8]>) -> Result<()> {
-        if let Some(buffer) = self.send_buffer.as_mut() {
-            buffer.extend_from_slice(data.as_ref());
-            Ok(())
-        } else {
-            self.interface.write(data.as_ref())
+        match self.protocol.queue(data.as_ref()) {
+            Some(due) => self.interface.write(&due),
+            None => Ok(()),
         }
     }
 
     pub fn flush(&mut self) -> Result<()> {
-        if let Some(buffer) = self.send_buffer.as_mut() {
-            self.interface.write(buffer.as_slice())?;
-            buffer.clear();
+        if let Some(buffered) = self.protocol.take_buffered() {
+            self.interface.write(&buffered)?;
         }
 
-        Ok(())
+        self.interface.flush()
+    }
+
+    /// Send a captured command stream (e.g. a `.prn` file) unchanged, returns the bytes sent
+    pub fn replay<R: Read>(&mut self, mut reader: R) -> Result<u64> {
+        let mut buf = [0u8; 4096];
+        let mut sent = 0;
+
+        loop {
+            match reader.read(&mut buf)? {
+                0 => break,
+                n => {
+                    self.write(&buf[..n])?;
+                    sent += n as u64;
+                }
+            }
+        }
+
+        self.flush()?;
+        Ok(sent)
     }
 
     // // Todo: return `Reponse` type
@@ -115,3 +451,35 @@ impl<D: PTouchInterface> PTouchPrinter<D> {
     //     self.interface.read_vec()
     // }
 }
+
+#[test]
+fn buffered_io() {
+    use crate::{interface::TestInterface, job::PrintJob, media};
+
+    let media = media::by_product("DK-22205").unwrap();
+    let job = PrintJob::new().media(media).page(RasterBuffer::new(20));
+
+    let mut direct = PTouchPrinter::from_interface(TestInterface::default());
+    direct.print_job(&job).unwrap();
+
+    // Nothing written until the flush at the end of the job
+    let mut printer = PTouchPrinter::buffered(TestInterface::default(), DEFAULT_BUFFER_LIMIT);
+    assert!(printer.is_buffered());
+    job.send(&mut printer).unwrap();
+    assert!(printer.interface.sent.is_empty());
+    printer.flush().unwrap();
+    assert_eq!(printer.interface.sent, direct.interface.sent);
+
+    // A full buffer is written out early
+    let mut printer = PTouchPrinter::buffered(TestInterface::default(), 500);
+    job.send(&mut printer).unwrap();
+    assert!(!printer.interface.sent.is_empty());
+    assert!(direct.interface.sent.starts_with(&printer.interface.sent));
+    printer.write([0u8; 600]).unwrap();
+    printer.set_buffered(None).unwrap();
+    assert!(!printer.is_buffered());
+    assert_eq!(
+        printer.interface.sent.len(),
+        direct.interface.sent.len() + 600
+    );
+}
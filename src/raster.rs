@@ -1,30 +1,219 @@
 use std::ops::{Deref, DerefMut};
 
-/// Rasterbuffer containing rasterlines, currently only for the 720px wide printers.
-pub struct RasterBuffer(Vec<[u8; 720 / 8]>);
+use image::{DynamicImage, RgbImage};
+
+use crate::{media::Media, rasterize::Rasterizer};
+
+/// Print engine (head) of a printer, determines the width of a raster line
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Engine {
+    /// 128 dots @ 180dpi, handheld P-touch tape printers (18/24mm tape)
+    HandHeld,
+    /// 560 dots @ 360dpi, wide P-touch tape printers (36mm tape)
+    HandHeldWide,
+    /// 720 dots @ 300dpi, desk label printers (62mm media)
+    #[default]
+    DeskLabel,
+    /// 1296 dots @ 300dpi, wide desk label printers (102mm media)
+    DeskLabelWide,
+}
+
+impl Engine {
+    /// Number of dots (pins) on the print head
+    pub const fn dots(&self) -> u32 {
+        match self {
+            Self::HandHeld => 128,
+            Self::HandHeldWide => 560,
+            Self::DeskLabel => 720,
+            Self::DeskLabelWide => 1296,
+        }
+    }
+
+    /// Length of a single (uncompressed) raster line
+    pub const fn bytes_per_line(&self) -> usize {
+        self.dots() as usize / 8
+    }
+
+    /// Horizontal resolution of the print head
+    pub const fn dpi(&self) -> u16 {
+        match self {
+            Self::HandHeld => 180,
+            Self::HandHeldWide => 360,
+            Self::DeskLabel | Self::DeskLabelWide => 300,
+        }
+    }
+
+    /// P-touch tape engines use their own raster framing and require TIFF compression
+    pub const fn is_tape(&self) -> bool {
+        matches!(self, Self::HandHeld | Self::HandHeldWide)
+    }
+
+    /// Printable area of a TZe tape of `width` mm on the 128 dot handheld engine
+    pub const fn tape_print_area(&self, width: u8) -> Option<PrintArea> {
+        let (offset, dots) = match (self, width) {
+            (Self::HandHeld, 4) => (52, 24), // 3.5mm
+            (Self::HandHeld, 6) => (48, 32),
+            (Self::HandHeld, 9) => (39, 50),
+            (Self::HandHeld, 12) => (29, 70),
+            (Self::HandHeld, 18) => (8, 112),
+            (Self::HandHeld, 24) => (0, 128),
+            _ => return None,
+        };
+
+        Some(PrintArea { offset, dots })
+    }
+}
+
+/// Printable dots across the print head
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct PrintArea {
+    /// Unused dots before the first printable dot
+    pub offset: u32,
+    /// Number of printable dots
+    pub dots: u32,
+}
+
+/// Rasterbuffer containing rasterlines, sized for the print engine.
+pub struct RasterBuffer {
+    engine: Engine,
+    lines: Vec<Vec<u8>>,
+}
 
 impl RasterBuffer {
+    /// Raster buffer for the (720px wide) desk label printers
     pub fn new(height: u32) -> Self {
-        let mut pbuf = Vec::with_capacity(height as usize);
+        Self::with_engine(Engine::DeskLabel, height)
+    }
 
-        for _line in 0..height {
-            pbuf.push([0; 90]);
-        }
+    pub fn with_engine(engine: Engine, height: u32) -> Self {
+        let lines = vec![vec![0; engine.bytes_per_line()]; height as usize];
+
+        RasterBuffer { engine, lines }
+    }
 
-        RasterBuffer(pbuf)
+    pub fn engine(&self) -> Engine {
+        self.engine
+    }
+
+    pub fn bytes_per_line(&self) -> usize {
+        self.engine.bytes_per_line()
+    }
+
+    /// Set dot `x` on line `y`, dots outside of the buffer are ignored
+    pub fn set_dot(&mut self, x: u32, y: u32) {
+        let bytes = self.bytes_per_line();
+
+        if let Some(line) = self.lines.get_mut(y as usize) {
+            if x < self.engine.dots() {
+                line[bytes - 1 - (x / 8) as usize] |= 1 << (x % 8);
+            }
+        }
     }
 }
 
 impl DerefMut for RasterBuffer {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        &mut self.lines
     }
 }
 
 impl Deref for RasterBuffer {
-    type Target = Vec<[u8; 720 / 8]>;
+    type Target = Vec<Vec<u8>>;
 
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &self.lines
+    }
+}
+
+/// Two plane raster buffer for black/red printing on two-colour media (720px wide printers)
+pub struct TwoColourRasterBuffer {
+    pub black: RasterBuffer,
+    pub red: RasterBuffer,
+}
+
+impl TwoColourRasterBuffer {
+    pub fn new(height: u32) -> Self {
+        Self {
+            black: RasterBuffer::new(height),
+            red: RasterBuffer::new(height),
+        }
+    }
+
+    /// Separate an RGB image into black and red planes, placed within the printable area of
+    /// `media` as by `Rasterizer::rasterize_two_colour` with the default options
+    pub fn from_rgb(image: &RgbImage, media: &Media) -> Self {
+        Rasterizer::new(media).rasterize_two_colour(&DynamicImage::ImageRgb8(image.clone()))
+    }
+
+    pub fn height(&self) -> usize {
+        self.black.len()
+    }
+
+    /// Iterate over the (black, red) raster line pairs
+    pub fn iter(&self) -> impl Iterator<Item = (&[u8], &[u8])> {
+        self.black
+            .iter()
+            .zip(self.red.iter())
+            .map(|(black, red)| (black.as_slice(), red.as_slice()))
+    }
+}
+
+/// Pixels printed in red on two-colour media
+pub(crate) fn is_red(r: u8, g: u8, b: u8, threshold: u8) -> bool {
+    r > threshold && g <= threshold && b <= threshold
+}
+
+/// ITU-R BT.601 luma, as used by `image` for greyscale conversion
+pub(crate) fn luma(r: u8, g: u8, b: u8) -> u8 {
+    ((r as u32 * 299 + g as u32 * 587 + b as u32 * 114) / 1000) as u8
+}
+
+#[test]
+fn two_colour_separation() {
+    let mut image = RgbImage::from_pixel(3, 2, image::Rgb([255, 255, 255]));
+    image.put_pixel(0, 0, image::Rgb([0, 0, 0]));
+    image.put_pixel(1, 0, image::Rgb([220, 30, 40]));
+    image.put_pixel(2, 1, image::Rgb([100, 100, 100]));
+
+    let media = crate::media::by_product("DK-22251").unwrap();
+    let buffer = TwoColourRasterBuffer::from_rgb(&image, media);
+    assert_eq!(buffer.height(), 2);
+
+    // Centred in the printable area (dots 12..708), image x = 0 becomes dot 358
+    assert_eq!(buffer.black[0][45], 1 << 6);
+    assert_eq!(buffer.red[0][45], 1 << 7);
+    assert_eq!(buffer.black[1][44], 1 << 0);
+    assert_eq!(buffer.red[1], vec![0; 90]);
+    assert_eq!(
+        buffer.black[0].iter().map(|b| b.count_ones()).sum::<u32>(),
+        1
+    );
+
+    // Cropped to the printable area
+    let wide = RgbImage::from_pixel(720, 1, image::Rgb([0, 0, 0]));
+    let buffer = TwoColourRasterBuffer::from_rgb(&wide, media);
+    let dots: u32 = buffer.black[0].iter().map(|b| b.count_ones()).sum();
+    assert_eq!(dots, media.print_area.dots);
+    assert_eq!((buffer.black[0][0], buffer.black[0][89]), (0, 0));
+}
+
+#[test]
+fn engine_line_widths() {
+    for (engine, bytes) in [
+        (Engine::HandHeld, 16),
+        (Engine::HandHeldWide, 70),
+        (Engine::DeskLabel, 90),
+        (Engine::DeskLabelWide, 162),
+    ] {
+        let mut buffer = RasterBuffer::with_engine(engine, 2);
+        assert_eq!(buffer.bytes_per_line(), bytes);
+        assert!(buffer.iter().all(|line| line.len() == bytes));
+
+        buffer.set_dot(0, 1);
+        buffer.set_dot(engine.dots() - 1, 1);
+        buffer.set_dot(engine.dots(), 1);
+        assert_eq!(buffer[1][0], 0x80);
+        assert_eq!(buffer[1][bytes - 1], 0x01);
+        assert_eq!(buffer[1].iter().map(|b| b.count_ones()).sum::<u32>(), 2);
     }
 }
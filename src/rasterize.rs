@@ -0,0 +1,360 @@
+//! Image to raster conversion.
+//!
+//! Converts an image to a `RasterBuffer`, reducing it to black and white dots with the
+//! selected `Dither` algorithm and placing it within the printable area of the media. Images
+//! for two-colour media are converted to a `TwoColourRasterBuffer` in the same way.
+
+use image::{DynamicImage, GrayImage, RgbImage};
+
+use crate::{
+    media::Media,
+    raster::{self, Engine, PrintArea, RasterBuffer, TwoColourRasterBuffer},
+};
+
+/// Black and white reduction algorithm
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Dither {
+    /// Fixed threshold, pixels with a luma at or below the threshold are printed
+    Threshold(u8),
+    /// Threshold chosen per image using Otsu's method, for text and logos
+    #[default]
+    Otsu,
+    /// Floyd-Steinberg error diffusion, for photos
+    FloydSteinberg,
+    /// Atkinson error diffusion, more contrast than Floyd-Steinberg
+    Atkinson,
+    /// Ordered dithering using a 4x4 Bayer matrix
+    Bayer,
+}
+
+/// Horizontal placement of the image within the printable area
+#[derive(Copy, Clone, PartialEq, Eq, Debug, Default)]
+pub enum Align {
+    Left,
+    #[default]
+    Centre,
+    Right,
+}
+
+/// Image to raster converter
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rasterizer {
+    engine: Engine,
+    print_area: PrintArea,
+    dither: Dither,
+    invert: bool,
+    align: Align,
+    offset: i32,
+}
+
+impl Rasterizer {
+    /// Rasterizer for the printable area of `media`
+    pub fn new(media: &Media) -> Self {
+        Self::with_print_area(media.engine, media.print_area)
+    }
+
+    /// Rasterizer using the full width of the print head of `engine`
+    pub fn for_engine(engine: Engine) -> Self {
+        let print_area = PrintArea {
+            offset: 0,
+            dots: engine.dots(),
+        };
+
+        Self::with_print_area(engine, print_area)
+    }
+
+    pub fn with_print_area(engine: Engine, print_area: PrintArea) -> Self {
+        Self {
+            engine,
+            print_area,
+            dither: Dither::default(),
+            invert: false,
+            align: Align::default(),
+            offset: 0,
+        }
+    }
+
+    pub fn dither(mut self, dither: Dither) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    /// Print the light instead of the dark parts of the image
+    pub fn invert(mut self, invert: bool) -> Self {
+        self.invert = invert;
+        self
+    }
+
+    pub fn align(mut self, align: Align) -> Self {
+        self.align = align;
+        self
+    }
+
+    /// Additional shift in dots after alignment, dots outside of the printable area are dropped
+    pub fn offset(mut self, dots: i32) -> Self {
+        self.offset = dots;
+        self
+    }
+
+    /// Convert `image` into raster lines, one line per image row
+    pub fn rasterize(&self, image: &DynamicImage) -> RasterBuffer {
+        let mut luma = flatten(image);
+        if self.invert {
+            image::imageops::invert(&mut luma);
+        }
+
+        let mut buffer = RasterBuffer::with_engine(self.engine, luma.height());
+        self.place(&mut buffer, luma.width(), reduce(&luma, self.dither));
+
+        buffer
+    }
+
+    /// Convert `image` into black and red planes for two-colour media. Reddish pixels are
+    /// printed red, the rest is reduced to black and white as by `rasterize` (`invert` swaps
+    /// black and white only)
+    pub fn rasterize_two_colour(&self, image: &DynamicImage) -> TwoColourRasterBuffer {
+        let rgb = flatten_rgb(image);
+        let is_red = |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            raster::is_red(r, g, b, 128)
+        };
+
+        // Red pixels are white in the black plane
+        let mut luma = GrayImage::from_fn(rgb.width(), rgb.height(), |x, y| {
+            let [r, g, b] = rgb.get_pixel(x, y).0;
+            image::Luma([if is_red(x, y) {
+                255
+            } else {
+                raster::luma(r, g, b)
+            }])
+        });
+        if self.invert {
+            image::imageops::invert(&mut luma);
+        }
+
+        let black = reduce(&luma, self.dither)
+            .into_iter()
+            .filter(|&(x, y)| !is_red(x, y));
+        let red = rgb
+            .enumerate_pixels()
+            .filter(|&(x, y, _)| is_red(x, y))
+            .map(|(x, y, _)| (x, y));
+
+        let mut buffer = TwoColourRasterBuffer::new(rgb.height());
+        self.place(&mut buffer.black, rgb.width(), black);
+        self.place(&mut buffer.red, rgb.width(), red);
+
+        buffer
+    }
+
+    /// Set the `dots` of an image `width` pixels wide, aligned within the printable area
+    fn place(
+        &self,
+        buffer: &mut RasterBuffer,
+        width: u32,
+        dots: impl IntoIterator<Item = (u32, u32)>,
+    ) {
+        let area = self.print_area;
+        let start = area.offset as i64
+            + self.offset as i64
+            + match self.align {
+                Align::Left => 0,
+                Align::Centre => (area.dots as i64 - width as i64) / 2,
+                Align::Right => area.dots as i64 - width as i64,
+            };
+        let printable = area.offset as i64..(area.offset + area.dots) as i64;
+
+        for (x, y) in dots {
+            let x = start + x as i64;
+            if printable.contains(&x) {
+                buffer.set_dot(x as u32, y);
+            }
+        }
+    }
+}
+
+/// Greyscale image, transparent pixels become white
+fn flatten(image: &DynamicImage) -> GrayImage {
+    let luma_alpha = image.to_luma_alpha8();
+
+    GrayImage::from_fn(luma_alpha.width(), luma_alpha.height(), |x, y| {
+        let [l, a] = luma_alpha.get_pixel(x, y).0;
+        let l = (l as u32 * a as u32 + 255 * (255 - a as u32)) / 255;
+        image::Luma([l as u8])
+    })
+}
+
+/// Colour image, transparent pixels become white
+fn flatten_rgb(image: &DynamicImage) -> RgbImage {
+    let rgba = image.to_rgba8();
+
+    RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u32 * a as u32 + 255 * (255 - a as u32)) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    })
+}
+
+/// Reduce to black and white, returns the coordinates of the dots to print
+fn reduce(luma: &GrayImage, dither: Dither) -> Vec<(u32, u32)> {
+    match dither {
+        Dither::Threshold(threshold) => threshold_dots(luma, threshold),
+        Dither::Otsu => threshold_dots(luma, otsu_threshold(luma)),
+        Dither::FloydSteinberg => diffuse(
+            luma,
+            16.0,
+            &[(1, 0, 7.0), (-1, 1, 3.0), (0, 1, 5.0), (1, 1, 1.0)],
+        ),
+        Dither::Atkinson => diffuse(
+            luma,
+            8.0,
+            &[
+                (1, 0, 1.0),
+                (2, 0, 1.0),
+                (-1, 1, 1.0),
+                (0, 1, 1.0),
+                (1, 1, 1.0),
+                (0, 2, 1.0),
+            ],
+        ),
+        Dither::Bayer => {
+            const BAYER: [[u8; 4]; 4] =
+                [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+            luma.enumerate_pixels()
+                .filter(|(x, y, p)| {
+                    let threshold = BAYER[*y as usize % 4][*x as usize % 4] as u16 * 16 + 8;
+                    (p.0[0] as u16) < threshold
+                })
+                .map(|(x, y, _)| (x, y))
+                .collect()
+        }
+    }
+}
+
+fn threshold_dots(luma: &GrayImage, threshold: u8) -> Vec<(u32, u32)> {
+    luma.enumerate_pixels()
+        .filter(|(_, _, p)| p.0[0] <= threshold)
+        .map(|(x, y, _)| (x, y))
+        .collect()
+}
+
+/// Threshold maximising the between class variance of the histogram
+fn otsu_threshold(luma: &GrayImage) -> u8 {
+    let mut histogram = [0u64; 256];
+    for p in luma.pixels() {
+        histogram[p.0[0] as usize] += 1;
+    }
+
+    let total: u64 = histogram.iter().sum();
+    let sum: f64 = histogram
+        .iter()
+        .enumerate()
+        .map(|(i, &n)| i as f64 * n as f64)
+        .sum();
+
+    let (mut best, mut best_variance) = (127, -1.0);
+    let (mut weight_bg, mut sum_bg) = (0u64, 0.0);
+
+    for (t, &n) in histogram.iter().enumerate() {
+        weight_bg += n;
+        sum_bg += t as f64 * n as f64;
+
+        let weight_fg = total - weight_bg;
+        if weight_bg == 0 || weight_fg == 0 {
+            continue;
+        }
+
+        let mean_bg = sum_bg / weight_bg as f64;
+        let mean_fg = (sum - sum_bg) / weight_fg as f64;
+        let variance = weight_bg as f64 * weight_fg as f64 * (mean_bg - mean_fg).powi(2);
+
+        if variance > best_variance {
+            best = t as u8;
+            best_variance = variance;
+        }
+    }
+
+    best
+}
+
+/// Error diffusion dithering, `kernel` holds (dx, dy, weight) with weights relative to `divisor`
+fn diffuse(luma: &GrayImage, divisor: f32, kernel: &[(i32, i32, f32)]) -> Vec<(u32, u32)> {
+    let (width, height) = luma.dimensions();
+    let mut values: Vec<f32> = luma.pixels().map(|p| p.0[0] as f32).collect();
+    let mut dots = Vec::new();
+
+    for y in 0..height {
+        for x in 0..width {
+            let old = values[(y * width + x) as usize];
+            let new = if old < 128.0 { 0.0 } else { 255.0 };
+            if new == 0.0 {
+                dots.push((x, y));
+            }
+
+            let error = (old - new) / divisor;
+            for &(dx, dy, weight) in kernel {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx >= 0 && nx < width as i32 && ny < height as i32 {
+                    values[(ny as u32 * width + nx as u32) as usize] += error * weight;
+                }
+            }
+        }
+    }
+
+    dots
+}
+
+#[test]
+fn rasterize_image() {
+    use crate::media;
+
+    let grey = |w, h, l| DynamicImage::ImageLuma8(GrayImage::from_pixel(w, h, image::Luma([l])));
+    let count =
+        |buffer: &RasterBuffer| buffer.iter().flatten().map(|b| b.count_ones()).sum::<u32>();
+
+    // Otsu separates the two clusters, the dark stripe is printed
+    let mut image = GrayImage::from_pixel(8, 1, image::Luma([200]));
+    for x in 0..3 {
+        image.put_pixel(x, 0, image::Luma([60]));
+    }
+    let image = DynamicImage::ImageLuma8(image);
+    let rasterizer = Rasterizer::for_engine(Engine::DeskLabel).align(Align::Left);
+    assert_eq!(rasterizer.rasterize(&image)[0][89], 0b111);
+    assert_eq!(
+        rasterizer.clone().invert(true).rasterize(&image)[0][89],
+        0b1111_1000
+    );
+
+    // Dithered mid grey prints roughly half of the dots
+    for dither in [Dither::FloydSteinberg, Dither::Atkinson, Dither::Bayer] {
+        let rasterizer = Rasterizer::for_engine(Engine::DeskLabel).dither(dither);
+        let dots = count(&rasterizer.rasterize(&grey(64, 64, 128)));
+        assert!((1800..2300).contains(&dots), "{dither:?}: {dots}");
+    }
+
+    // Placed within, and cropped to, the printable area of the media
+    let media = media::by_product("DK-22210").unwrap();
+    let black = grey(400, 1, 0);
+    let buffer = Rasterizer::new(media).rasterize(&black);
+    assert_eq!(count(&buffer), media.print_area.dots);
+    let right = Rasterizer::new(media)
+        .align(Align::Right)
+        .offset(-10)
+        .rasterize(&grey(1, 1, 0));
+    assert_eq!(right[0][2], 1 << 7);
+
+    // Red pixels go to the red plane, the rest is placed and inverted like single colour pages
+    let mut rgb = RgbImage::from_pixel(4, 1, image::Rgb([255, 255, 255]));
+    rgb.put_pixel(0, 0, image::Rgb([0, 0, 0]));
+    rgb.put_pixel(1, 0, image::Rgb([220, 30, 40]));
+    let rgb = DynamicImage::ImageRgb8(rgb);
+    let rasterizer = Rasterizer::new(media::by_product("DK-22251").unwrap()).align(Align::Left);
+    let buffer = rasterizer.rasterize_two_colour(&rgb);
+    assert_eq!((buffer.black[0][88], buffer.red[0][88]), (1 << 4, 1 << 5));
+    let buffer = rasterizer.invert(true).rasterize_two_colour(&rgb);
+    assert_eq!(
+        (buffer.black[0][88], buffer.red[0][88]),
+        (0b1100_0000, 1 << 5)
+    );
+}
@@ -1,6 +1,12 @@
 use std::fmt::Debug;
 
-use crate::{prelude::PTouchTcpInterface, printer::PTouchPrinter, PTouchError, Result};
+use crate::{
+    commands::{Commands, VariousMode},
+    interface::PTouchInterface,
+    printer::PTouchPrinter,
+    raster::Engine,
+    PTouchError, Result,
+};
 use bitflags::bitflags;
 use num_enum::{FromPrimitive, IntoPrimitive};
 
@@ -8,23 +14,48 @@ pub trait GetStatus {
     fn get_status(&mut self) -> Result<Status>;
 }
 
-impl GetStatus for PTouchPrinter<PTouchTcpInterface> {
+/// Status over the data channel, works for every interface. Pending notifications (e.g. of a
+/// previous print) are skipped. Network printers can also be queried out-of-band using
+/// `PTouchPrinter::get_snmp_status`
+impl<D: PTouchInterface> GetStatus for PTouchPrinter<D> {
     fn get_status(&mut self) -> Result<Status> {
-        self.get_snmp_status()
+        self.status_req()?;
+        self.flush()?;
+
+        loop {
+            let status = self.read_status()?;
+            if status.status_type == DeviceStatus::Reply {
+                return Ok(status);
+            }
+        }
     }
 }
 
 /// Device status message
 #[derive(Clone, PartialEq, Debug)]
 pub struct Status {
+    /// Print head mark, fixed at 0x80
+    pub head_mark: u8,
+    /// Size of the status frame, fixed at 32
+    pub size: u8,
+    /// Series code, '4' (0x34) for the QL series
+    pub series: u8,
     pub model: Model,
+    /// Error information 1 and 2 combined (1 in the low byte)
     pub error_status: ErrorStatus,
+    pub error_info_1: ErrorInfo1,
+    pub error_info_2: ErrorInfo2,
     pub status_type: DeviceStatus,
 
     pub media_width: u8,
     pub media_length: u8,
     pub media_kind: MediaKind,
-    // pub phase: Phase,
+    /// Various mode as last set
+    pub mode: VariousMode,
+    pub phase: Phase,
+    /// Phase number, 0 is "waiting to receive" or "printing" depending on the phase
+    pub phase_number: u16,
+    pub notification: Notification,
     pub tape_colour: TapeColour,
     pub text_colour: TextColour,
 }
@@ -43,24 +74,39 @@ impl TryFrom<&[u8]> for Status {
 impl From<[u8; 32]> for Status {
     fn from(r: [u8; 32]) -> Self {
         Self {
+            head_mark: r[0],
+            size: r[1],
+            series: r[3],
             model: Model::from(r[4]),
             error_status: ErrorStatus::from_bits_truncate(u16::from_le_bytes([r[8], r[9]])),
+            error_info_1: ErrorInfo1::from_bits_retain(r[8]),
+            error_info_2: ErrorInfo2::from_bits_retain(r[9]),
 
             media_width: r[10],
             media_length: r[17],
             media_kind: MediaKind::from(r[11]),
+            mode: VariousMode::from_bits_retain(r[15]),
             status_type: DeviceStatus::from(r[18]),
-            // phase: Phase::from(r[20]),
+            phase: Phase::from(r[19]),
+            phase_number: u16::from_be_bytes([r[20], r[21]]),
+            notification: Notification::from(r[22]),
             tape_colour: TapeColour::from(r[24]),
             text_colour: TextColour::from(r[25]),
         }
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug, FromPrimitive)]
+#[derive(Clone, Copy, PartialEq, Debug, FromPrimitive, IntoPrimitive)]
 #[repr(u8)]
 pub enum Model {
     // Standard 300(600)DPI desk printers
+    /// QL-500 and QL-550, both report the same model code
+    QL500 = 0x4f,
+    QL560 = 0x31,
+    QL570 = 0x32,
+    QL580N = 0x33,
+    QL650TD = 0x51,
+    QL700 = 0x35,
     QL710W = 0x36,
     QL720NW = 0x37,
     QL800 = 0x38,
@@ -68,41 +114,187 @@ pub enum Model {
     QL820NWB = 0x41,
     QL600 = 0x47,
 
-    // Here for future support/reference only (180DPI tape printers)
+    // Wide (102mm) 300(600)DPI desk printers
+    QL1050 = 0x50,
+    QL1060N = 0x34,
+    QL1100 = 0x43,
+    QL1110NWB = 0x44,
+    QL1115NWB = 0x45,
+
+    // 180DPI tape printers
     PTH500 = 0x64,
     PTE500 = 0x65,
+    PTE550W = 0x66,
     PTP700 = 0x67,
+    PTP750W = 0x68,
+
+    // 360DPI wide tape printers
+    PTP900W = 0x69,
+    PTP950NW = 0x70,
+    PTP910BT = 0x78,
 
     #[num_enum(catch_all)]
     Unknown(u8),
 }
 
+/// What a printer model can do
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct Capabilities {
+    /// Print engine, determines the head width (dots) and resolution
+    pub engine: Engine,
+    /// Black/red printing on two-colour media
+    pub two_colour: bool,
+    /// Automatic (full) cutter
+    pub auto_cut: bool,
+    /// Cuts the tape, but not the backing paper
+    pub half_cut: bool,
+    /// Chain printing, i.e. no feed and cut after the last label
+    pub chain_printing: bool,
+    /// TIFF (PackBits) compressed raster lines
+    pub compression: bool,
+    /// Double resolution in the feed direction
+    pub high_res: bool,
+    /// Maximum printable length in mm
+    pub max_length: u32,
+    /// Network interface, status and information over SNMP
+    pub network: bool,
+}
+
+impl Capabilities {
+    /// Number of dots on the print head
+    pub fn dots(&self) -> u32 {
+        self.engine.dots()
+    }
+
+    pub fn dpi(&self) -> u16 {
+        self.engine.dpi()
+    }
+
+    /// Maximum printable length in raster lines (at normal resolution)
+    pub fn max_lines(&self) -> u32 {
+        self.max_length * self.dpi() as u32 * 10 / 254
+    }
+}
+
+const fn desk_label(engine: Engine, max_length: u32) -> Capabilities {
+    Capabilities {
+        engine,
+        two_colour: false,
+        auto_cut: true,
+        half_cut: false,
+        chain_printing: true,
+        compression: true,
+        high_res: true,
+        max_length,
+        network: false,
+    }
+}
+
+const fn tape(engine: Engine, max_length: u32) -> Capabilities {
+    Capabilities {
+        engine,
+        two_colour: false,
+        auto_cut: true,
+        half_cut: true,
+        chain_printing: true,
+        compression: true,
+        high_res: false,
+        max_length,
+        network: false,
+    }
+}
+
 impl Model {
+    /// Capabilities of the model, `None` for unknown models
+    pub fn capabilities(&self) -> Option<Capabilities> {
+        let ql = desk_label(Engine::DeskLabel, 1000);
+        let ql_wide = desk_label(Engine::DeskLabelWide, 3000);
+        let pt = tape(Engine::HandHeld, 1000);
+        let pt_wide = tape(Engine::HandHeldWide, 2000);
+
+        let capabilities = match self {
+            Self::QL500 => Capabilities {
+                // Shared with the QL-550 which has a cutter, the QL-500 (manual cutter)
+                // ignores the auto cut flag
+                chain_printing: false,
+                compression: false,
+                high_res: false,
+                ..ql
+            },
+            Self::QL560 => Capabilities {
+                compression: false,
+                high_res: false,
+                ..ql
+            },
+            Self::QL570 | Self::QL700 => Capabilities {
+                compression: false,
+                ..ql
+            },
+            Self::QL580N => Capabilities {
+                network: true,
+                ..ql
+            },
+            Self::QL650TD => Capabilities {
+                compression: false,
+                ..ql
+            },
+            Self::QL600 => ql,
+            Self::QL710W | Self::QL720NW => Capabilities {
+                network: true,
+                ..ql
+            },
+            Self::QL800 => Capabilities {
+                two_colour: true,
+                compression: false,
+                ..ql
+            },
+            Self::QL810W | Self::QL820NWB => Capabilities {
+                two_colour: true,
+                network: true,
+                ..ql
+            },
+            Self::QL1050 | Self::QL1100 => ql_wide,
+            Self::QL1060N | Self::QL1110NWB | Self::QL1115NWB => Capabilities {
+                network: true,
+                ..ql_wide
+            },
+            Self::PTH500 | Self::PTE500 | Self::PTP700 => Capabilities {
+                half_cut: false,
+                ..pt
+            },
+            Self::PTE550W | Self::PTP750W => Capabilities {
+                max_length: 2000,
+                network: true,
+                ..pt
+            },
+            Self::PTP900W | Self::PTP950NW => Capabilities {
+                high_res: true,
+                network: true,
+                ..pt_wide
+            },
+            Self::PTP910BT => Capabilities {
+                high_res: true,
+                ..pt_wide
+            },
+            Self::Unknown(_) => return None,
+        };
+
+        Some(capabilities)
+    }
+
+    /// Horizontal resolution, 0 for unknown models
     pub fn dpi(&self) -> u16 {
-        match self {
-            Self::QL710W
-            | Self::QL720NW
-            | Self::QL800
-            | Self::QL810W
-            | Self::QL820NWB
-            | Self::QL600 => 300,
-            Self::PTH500 | Self::PTE500 | Self::PTP700 => 180,
-            Self::Unknown(_) => 0,
-        }
+        self.capabilities().map(|c| c.dpi()).unwrap_or(0)
+    }
+
+    /// Print engine of the printer, `None` for unknown models
+    pub fn engine(&self) -> Option<Engine> {
+        self.capabilities().map(|c| c.engine)
     }
 
     /// Does the printer support double vertical resolution (i.e. half speed)
     pub fn support_double_dpi(&self) -> bool {
-        match self {
-            Self::QL710W
-            | Self::QL720NW
-            | Self::QL800
-            | Self::QL810W
-            | Self::QL820NWB
-            | Self::QL600 => true,
-            Self::PTH500 | Self::PTE500 | Self::PTP700 => false,
-            Self::Unknown(_) => false,
-        }
+        self.capabilities().is_some_and(|c| c.high_res)
     }
 }
 
@@ -137,6 +329,102 @@ impl Debug for ErrorStatus {
     }
 }
 
+impl ErrorStatus {
+    /// Conditions needing service, rather than the user fixing the printer (cover, media, ...)
+    pub const FATAL: Self = Self::HIGH_VOLT
+        .union(Self::FAN_MOTOR_ERROR)
+        .union(Self::SYSTEM_ERROR);
+
+    /// Can the user fix the printer, e.g. by closing the cover or replacing the media
+    pub fn is_recoverable(&self) -> bool {
+        !self.intersects(Self::FATAL)
+    }
+
+    fn description(flag: Self) -> &'static str {
+        match flag {
+            Self::NO_MEDIA => "no media",
+            Self::END_OF_MEDIA => "end of media",
+            Self::CUTTER_JAM => "cutter jam",
+            Self::WEAK_BATT => "weak battery",
+            Self::IN_USE => "printer in use",
+            Self::PRINTER_OFF => "printer turned off",
+            Self::HIGH_VOLT => "high-voltage adapter",
+            Self::FAN_MOTOR_ERROR => "fan motor error",
+            Self::WRONG_MEDIA => "replace media",
+            Self::EXP_BUFFER_FULL => "expansion buffer full",
+            Self::COMMS_ERROR => "communication error",
+            Self::COMMS_BUFFFER_FULL => "communication buffer full",
+            Self::COVER_OPEN => "cover open",
+            Self::OVERHEAT => "overheating",
+            Self::MEDIA_END => "media cannot be fed",
+            Self::SYSTEM_ERROR => "system error",
+            _ => "unknown error",
+        }
+    }
+}
+
+/// Human readable list of the error conditions, e.g. "cover open, no media"
+impl std::fmt::Display for ErrorStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return write!(f, "no error");
+        }
+
+        let descriptions: Vec<_> = self.iter().map(Self::description).collect();
+        write!(f, "{}", descriptions.join(", "))
+    }
+}
+
+bitflags! {
+    /// Error information 1 (status byte 8)
+    #[derive(Copy, Clone, PartialEq)]
+    pub struct ErrorInfo1: u8 {
+        const NO_MEDIA = 0x01;
+        /// Die-cut labels only
+        const END_OF_MEDIA = 0x02;
+        const CUTTER_JAM = 0x04;
+        /// P-touch only
+        const WEAK_BATTERY = 0x08;
+        const PRINTER_IN_USE = 0x10;
+        const PRINTER_TURNED_OFF = 0x20;
+        const HIGH_VOLTAGE_ADAPTER = 0x40;
+        const FAN_MOTOR_ERROR = 0x80;
+    }
+}
+
+bitflags! {
+    /// Error information 2 (status byte 9)
+    #[derive(Copy, Clone, PartialEq)]
+    pub struct ErrorInfo2: u8 {
+        const REPLACE_MEDIA = 0x01;
+        const EXPANSION_BUFFER_FULL = 0x02;
+        const COMMUNICATION_ERROR = 0x04;
+        const COMMUNICATION_BUFFER_FULL = 0x08;
+        const COVER_OPEN = 0x10;
+        /// Overheating on P-touch printers
+        const CANCEL_KEY = 0x20;
+        /// Also set when the end of the media is detected
+        const MEDIA_CANNOT_BE_FED = 0x40;
+        const SYSTEM_ERROR = 0x80;
+    }
+}
+
+impl Debug for ErrorInfo1 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.iter_names().map(|(s, _v)| s))
+            .finish()
+    }
+}
+
+impl Debug for ErrorInfo2 {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries(self.iter_names().map(|(s, _v)| s))
+            .finish()
+    }
+}
+
 /// Kind of media loaded in printer
 #[derive(Copy, Clone, PartialEq, Debug, FromPrimitive, IntoPrimitive)]
 #[repr(u8)]
@@ -170,10 +458,32 @@ pub enum DeviceStatus {
     Unknown(u8),
 }
 
-/// Tape colour enumerations
+/// Phase type, the printer alternates between receiving data and printing
 #[derive(Copy, Clone, PartialEq, Debug, FromPrimitive)]
 #[repr(u8)]
+pub enum Phase {
+    Receiving = 0x00,
+    Printing = 0x01,
 
+    #[num_enum(catch_all)]
+    Unknown(u8),
+}
+
+/// Notification number, sent with `DeviceStatus::Notification`
+#[derive(Copy, Clone, PartialEq, Debug, FromPrimitive)]
+#[repr(u8)]
+pub enum Notification {
+    NotAvailable = 0x00,
+    CoolingStarted = 0x03,
+    CoolingFinished = 0x04,
+
+    #[num_enum(catch_all)]
+    Unknown(u8),
+}
+
+/// Tape colour enumerations
+#[derive(Copy, Clone, PartialEq, Debug, FromPrimitive)]
+#[repr(u8)]
 pub enum TapeColour {
     White = 0x01,
     Other = 0x02,
@@ -220,6 +530,8 @@ pub enum TextColour {
     Cleaning = 0xf0,
     Stencil = 0xF1,
     Other = 0x02,
+    /// Two-colour (black/red) DK roll, reported by the QL-800 series
+    BlackRed = 0x81,
 
     #[num_enum(default)]
     Unknown = 0,
@@ -228,8 +540,106 @@ pub enum TextColour {
 #[test]
 fn parse_status() {
     // 80204234393004000000320a00001b0000000000000000000001000000000000
-    let _status_example = [
+    let status_example = [
+        128, 32, 66, 52, 57, 48, 4, 0, 0, 0, 50, 10, 0, 0, 27, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0,
+        0, 0, 0, 0, 0,
+    ];
+
+    let status = Status::from(status_example);
+    assert_eq!(
+        (status.head_mark, status.size, status.series),
+        (0x80, 32, b'4')
+    );
+    assert_eq!(status.model, Model::QL810W);
+    assert!(status.error_status.is_empty());
+    assert_eq!(status.status_type, DeviceStatus::Reply);
+    assert_eq!(status.media_kind, MediaKind::ContinuousLengthTape);
+    assert_eq!((status.media_width, status.media_length), (50, 0));
+    assert_eq!(status.mode, VariousMode::empty());
+    assert_eq!((status.phase, status.phase_number), (Phase::Receiving, 0));
+    assert_eq!(status.notification, Notification::NotAvailable);
+
+    // QL-820NWB, cover opened while printing 29x90 labels with auto cut
+    let mut frame = [0u8; 32];
+    frame[..8].copy_from_slice(&[0x80, 0x20, 0x42, 0x34, 0x41, 0x30, 0x30, 0x00]);
+    frame[8..12].copy_from_slice(&[0x01, 0x10, 29, 0x4b]);
+    frame[14..20].copy_from_slice(&[0x3f, 0x40, 0x00, 90, 0x02, 0x01]);
+    frame[20..23].copy_from_slice(&[0x00, 0x00, 0x03]);
+
+    let status = Status::from(frame);
+    assert_eq!(status.model, Model::QL820NWB);
+    assert_eq!(
+        status.error_status,
+        ErrorStatus::NO_MEDIA | ErrorStatus::COVER_OPEN
+    );
+    assert_eq!(status.error_info_1, ErrorInfo1::NO_MEDIA);
+    assert_eq!(status.error_info_2, ErrorInfo2::COVER_OPEN);
+    assert_eq!(status.status_type, DeviceStatus::Error);
+    assert_eq!(status.media_kind, MediaKind::DieCutLabels);
+    assert_eq!((status.media_width, status.media_length), (29, 90));
+    assert_eq!(status.mode, VariousMode::AUTO_CUT);
+    assert_eq!(status.phase, Phase::Printing);
+    assert_eq!(status.notification, Notification::CoolingStarted);
+
+    frame[20..22].copy_from_slice(&[0x01, 0x02]);
+    assert_eq!(Status::from(frame).phase_number, 0x0102);
+}
+
+#[test]
+fn get_status_over_data_channel() {
+    use crate::interface::TestInterface;
+
+    let frame = [
         128, 32, 66, 52, 57, 48, 4, 0, 0, 0, 50, 10, 0, 0, 27, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0,
         0, 0, 0, 0, 0,
     ];
+    let mut printer = PTouchPrinter::from_interface(TestInterface {
+        reply: frame.into_iter().collect(),
+        ..Default::default()
+    });
+
+    let status = printer.get_status().unwrap();
+    assert_eq!(printer.interface.sent, [0x1b, 0x69, 0x53]);
+    assert_eq!(status.media_width, 50);
+    assert_eq!(status.media_kind, MediaKind::ContinuousLengthTape);
+
+    // A truncated reply is an error, not a bogus status
+    printer.interface.reply = frame[..20].iter().copied().collect();
+    assert!(printer.get_status().is_err());
+}
+
+#[test]
+fn model_capabilities() {
+    let ql = Model::from(0x41).capabilities().unwrap();
+    assert_eq!(ql.engine, Engine::DeskLabel);
+    assert!(ql.two_colour && ql.network && ql.compression);
+    assert_eq!(ql.max_lines(), 11811);
+
+    let wide = Model::QL1110NWB.capabilities().unwrap();
+    assert_eq!((wide.dots(), wide.dpi()), (1296, 300));
+    assert!(!wide.two_colour);
+
+    assert!(!Model::QL800.capabilities().unwrap().compression);
+    assert!(!Model::QL650TD.capabilities().unwrap().compression);
+    // QL-500 and QL-550 report the same model code, only the QL-550 has a cutter
+    assert!(Model::QL500.capabilities().unwrap().auto_cut);
+    assert_eq!(Model::PTP950NW.engine(), Some(Engine::HandHeldWide));
+    assert_eq!(Model::from(0x66), Model::PTE550W);
+    assert_eq!(Model::PTP700.dpi(), 180);
+    assert!(Model::QL1050.support_double_dpi());
+
+    assert!(Model::Unknown(0x99).capabilities().is_none());
+    assert_eq!(Model::Unknown(0x99).dpi(), 0);
+}
+
+#[test]
+fn error_status_display() {
+    let error_status = ErrorStatus::COVER_OPEN | ErrorStatus::NO_MEDIA;
+    assert_eq!(error_status.to_string(), "no media, cover open");
+    assert!(error_status.is_recoverable());
+    assert!(!(error_status | ErrorStatus::SYSTEM_ERROR).is_recoverable());
+    assert_eq!(ErrorStatus::empty().to_string(), "no error");
+
+    let error = crate::PTouchError::Printer(error_status);
+    assert_eq!(error.to_string(), "printer error: no media, cover open");
 }